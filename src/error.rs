@@ -1,3 +1,5 @@
+use crate::CommandOutputStream;
+
 #[derive(Debug)]
 pub enum SshSessionError {
     SshSessionIsNotActive,
@@ -6,6 +8,29 @@ pub enum SshSessionError {
     SshAuthenticationError,
     Other(String),
     Timeout,
+    Cancelled,
+    ExpectTimeout(String),
+    ExpectEof(String),
+    RunAsPasswordRequired,
+    RunAsWrongPassword,
+    RunAsNotAllowed(String),
+    PersistentShellClosed,
+    BackgroundProcessNotFound(String),
+    // deserialize_error is None when the command exited with non-zero code
+    JsonCommandFailed {
+        exit_code: i32,
+        stderr: String,
+        deserialize_error: Option<String>,
+    },
+    OutputLimitExceeded {
+        stream: CommandOutputStream,
+        limit: usize,
+    },
+    ChecksumMismatch {
+        path: String,
+        local: String,
+        remote: String,
+    },
 }
 
 impl From<async_ssh2_lite::Error> for SshSessionError {
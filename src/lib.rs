@@ -11,16 +11,35 @@ mod ssh_session_single_threaded;
 pub use ssh_session_single_threaded::*;
 mod ssh_session_wrapper;
 pub use ssh_session_wrapper::*;
+mod ssh_expect_session;
+pub use ssh_expect_session::*;
+mod ssh_command;
+pub use ssh_command::*;
+mod ssh_persistent_shell;
+pub use ssh_persistent_shell::*;
+mod ssh_background_process;
+pub use ssh_background_process::*;
+mod sftp;
+pub use sftp::*;
+mod transfer;
+pub use transfer::*;
 
 pub type SshAsyncSession = async_ssh2_lite::AsyncSession<async_ssh2_lite::TokioTcpStream>;
 
 pub type SshAsyncChannel = async_ssh2_lite::AsyncChannel<async_ssh2_lite::TokioTcpStream>;
 
+pub type SshAsyncSftp = async_ssh2_lite::AsyncSftp<async_ssh2_lite::TokioTcpStream>;
+
+pub type SshAsyncSftpFile = async_ssh2_lite::AsyncFile<async_ssh2_lite::TokioTcpStream>;
+
+pub extern crate regex;
 pub extern crate ssh2;
 mod port_forward;
 pub use port_forward::*;
 mod ssh_sessions_pool;
 pub use ssh_sessions_pool::*;
+mod ssh_fan_out;
+pub use ssh_fan_out::*;
 
 pub mod ssh_settings;
 
@@ -0,0 +1,2 @@
+mod ssh_sftp;
+pub use ssh_sftp::*;
@@ -0,0 +1,289 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures::Future;
+use ssh2::{FileStat, OpenFlags, OpenType};
+
+use crate::{SshAsyncSftp, SshAsyncSftpFile, SshSessionError, SshSessionInnerL};
+
+const SSH_FX_NO_SUCH_FILE: i32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct SshSftpEntry {
+    pub name: String,
+    pub path: String,
+    pub stat: FileStat,
+}
+
+impl SshSftpEntry {
+    pub fn is_dir(&self) -> bool {
+        self.stat.is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.stat.is_file()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.stat.file_type().is_symlink()
+    }
+}
+
+// SFTP client on top of the session. Every operation is limited with operation_timeout.
+// If operation times out - session is disconnected the same way as other operations do
+#[derive(Clone)]
+pub struct SshSftp {
+    sftp: Arc<SshAsyncSftp>,
+    session: Arc<SshSessionInnerL>,
+    operation_timeout: Duration,
+}
+
+impl SshSftp {
+    pub fn new(
+        sftp: Arc<SshAsyncSftp>,
+        session: Arc<SshSessionInnerL>,
+        operation_timeout: Duration,
+    ) -> Self {
+        Self {
+            sftp,
+            session,
+            operation_timeout,
+        }
+    }
+
+    pub fn get_operation_timeout(&self) -> Duration {
+        self.operation_timeout
+    }
+
+    pub async fn list_dir(&self, path: &str) -> Result<Vec<SshSftpEntry>, SshSessionError> {
+        let items = self.execute(self.sftp.readdir(to_sftp_path(path))).await?;
+
+        let mut result = Vec::with_capacity(items.len());
+
+        for (item_path, stat) in items {
+            let name = match item_path.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            if name == "." || name == ".." {
+                continue;
+            }
+
+            result.push(SshSftpEntry {
+                name,
+                path: item_path.to_string_lossy().to_string(),
+                stat,
+            });
+        }
+
+        Ok(result)
+    }
+
+    pub async fn stat(&self, path: &str) -> Result<FileStat, SshSessionError> {
+        self.execute(self.sftp.stat(to_sftp_path(path))).await
+    }
+
+    // Does not follow symlinks
+    pub async fn lstat(&self, path: &str) -> Result<FileStat, SshSessionError> {
+        self.execute(self.sftp.lstat(to_sftp_path(path))).await
+    }
+
+    // None if the file does not exist. Other errors (permission denied, IO) are returned as is
+    pub async fn try_stat(&self, path: &str) -> Result<Option<FileStat>, SshSessionError> {
+        match self.stat(path).await {
+            Ok(stat) => Ok(Some(stat)),
+            Err(err) if is_no_such_file(&err) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn exists(&self, path: &str) -> Result<bool, SshSessionError> {
+        match self.lstat(path).await {
+            Ok(_) => Ok(true),
+            Err(err) if is_no_such_file(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn mkdir(&self, path: &str, mode: i32) -> Result<(), SshSessionError> {
+        self.execute(self.sftp.mkdir(to_sftp_path(path), mode))
+            .await
+    }
+
+    // Same as mkdir -p
+    pub async fn mkdir_all(&self, path: &str, mode: i32) -> Result<(), SshSessionError> {
+        let mut current = PathBuf::new();
+
+        for component in to_sftp_path(path).components() {
+            current.push(component);
+
+            if component == std::path::Component::RootDir {
+                continue;
+            }
+
+            let current_path = current.to_string_lossy();
+
+            match self.try_stat(current_path.as_ref()).await? {
+                Some(stat) => {
+                    if !stat.is_dir() {
+                        return Err(SshSessionError::Other(format!(
+                            "Can not create directory {}. {} is not a directory",
+                            path, current_path
+                        )));
+                    }
+                }
+                None => self.mkdir(current_path.as_ref(), mode).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn remove_file(&self, path: &str) -> Result<(), SshSessionError> {
+        self.execute(self.sftp.unlink(to_sftp_path(path))).await
+    }
+
+    // Directory must be empty
+    pub async fn remove_dir(&self, path: &str) -> Result<(), SshSessionError> {
+        self.execute(self.sftp.rmdir(to_sftp_path(path))).await
+    }
+
+    // Most servers speak SFTP v3 where rename fails if destination exists
+    pub async fn rename(&self, src: &str, dst: &str) -> Result<(), SshSessionError> {
+        self.execute(self.sftp.rename(to_sftp_path(src), to_sftp_path(dst), None))
+            .await
+    }
+
+    pub async fn read_link(&self, path: &str) -> Result<String, SshSessionError> {
+        let result = self.execute(self.sftp.readlink(to_sftp_path(path))).await?;
+        Ok(result.to_string_lossy().to_string())
+    }
+
+    // Creates symlink at path pointing to target
+    pub async fn symlink(&self, path: &str, target: &str) -> Result<(), SshSessionError> {
+        self.execute(self.sftp.symlink(to_sftp_path(path), Path::new(target)))
+            .await
+    }
+
+    pub async fn real_path(&self, path: &str) -> Result<String, SshSessionError> {
+        let result = self.execute(self.sftp.realpath(to_sftp_path(path))).await?;
+        Ok(result.to_string_lossy().to_string())
+    }
+
+    pub async fn chmod(&self, path: &str, mode: u32) -> Result<(), SshSessionError> {
+        let stat = FileStat {
+            size: None,
+            uid: None,
+            gid: None,
+            perm: Some(mode),
+            atime: None,
+            mtime: None,
+        };
+
+        self.set_stat(path, stat).await
+    }
+
+    pub async fn chown(&self, path: &str, uid: u32, gid: u32) -> Result<(), SshSessionError> {
+        let stat = FileStat {
+            size: None,
+            uid: Some(uid),
+            gid: Some(gid),
+            perm: None,
+            atime: None,
+            mtime: None,
+        };
+
+        self.set_stat(path, stat).await
+    }
+
+    // Fields which are None are not changed
+    pub async fn set_stat(&self, path: &str, stat: FileStat) -> Result<(), SshSessionError> {
+        self.execute(self.sftp.setstat(to_sftp_path(path), stat))
+            .await
+    }
+
+    pub async fn open(&self, path: &str) -> Result<SshAsyncSftpFile, SshSessionError> {
+        self.execute(self.sftp.open(to_sftp_path(path))).await
+    }
+
+    // Creates or truncates the file
+    pub async fn create(&self, path: &str, mode: i32) -> Result<SshAsyncSftpFile, SshSessionError> {
+        self.open_with_flags(
+            path,
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+            mode,
+        )
+        .await
+    }
+
+    // File implements AsyncRead, AsyncWrite and AsyncSeek from futures
+    pub async fn open_with_flags(
+        &self,
+        path: &str,
+        flags: OpenFlags,
+        mode: i32,
+    ) -> Result<SshAsyncSftpFile, SshSessionError> {
+        self.execute(
+            self.sftp
+                .open_mode(to_sftp_path(path), flags, mode, OpenType::File),
+        )
+        .await
+    }
+
+    async fn execute<TResult>(
+        &self,
+        future: impl Future<Output = Result<TResult, async_ssh2_lite::Error>>,
+    ) -> Result<TResult, SshSessionError> {
+        match tokio::time::timeout(self.operation_timeout, future).await {
+            Ok(result) => Ok(result?),
+            Err(_) => {
+                self.session.disconnect("Sftp operation timeout").await;
+                Err(SshSessionError::Timeout)
+            }
+        }
+    }
+}
+
+// SSH_FX_NO_SUCH_FILE. ssh2 reports it as io::ErrorKind::NotFound when it is converted to io::Error
+fn is_no_such_file(err: &SshSessionError) -> bool {
+    match err {
+        SshSessionError::SshError(async_ssh2_lite::Error::Ssh2(err)) => {
+            err.code() == ssh2::ErrorCode::SFTP(SSH_FX_NO_SUCH_FILE)
+        }
+        SshSessionError::SshError(async_ssh2_lite::Error::Io(err)) => {
+            err.kind() == std::io::ErrorKind::NotFound
+        }
+        SshSessionError::StdIoStreamError(err) => err.kind() == std::io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
+// SFTP servers resolve relative paths from the home directory, so ~/ is just dropped
+pub(crate) fn to_sftp_path(path: &str) -> &Path {
+    if path == "~" {
+        return Path::new(".");
+    }
+
+    match path.strip_prefix("~/") {
+        Some(path) => Path::new(path),
+        None => Path::new(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::to_sftp_path;
+
+    #[test]
+    fn test_to_sftp_path() {
+        assert_eq!(to_sftp_path("~"), Path::new("."));
+        assert_eq!(to_sftp_path("~/app/config"), Path::new("app/config"));
+        assert_eq!(to_sftp_path("/etc/hosts"), Path::new("/etc/hosts"));
+    }
+}
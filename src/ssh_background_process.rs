@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+use crate::{shell_quote, SshSession, SshSessionError};
+
+// Remote directory which keeps state of background processes. Relative to $HOME
+pub const BACKGROUND_PROCESSES_DIR: &str = ".my-ssh/processes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshBackgroundProcessStatus {
+    Running,
+    Exited(i32),
+    // Process is gone without reporting the exit code (killed together with its process group, host rebooted)
+    Lost,
+}
+
+// Handle of a detached remote process. It can be restored by id from any session to the same host
+#[derive(Debug, Clone)]
+pub struct SshBackgroundProcess {
+    pub id: String,
+    pub pid: u32,
+    pub state_dir: String,
+    pub log_path: String,
+}
+
+impl SshBackgroundProcess {
+    fn from_output(id: &str, output: &str) -> Result<Self, SshSessionError> {
+        let mut lines = output.lines();
+
+        let state_dir = lines.next().map(|itm| itm.trim().to_string());
+        let pid = lines.next().and_then(|itm| itm.trim().parse().ok());
+
+        match (state_dir, pid) {
+            (Some(state_dir), Some(pid)) => Ok(Self {
+                id: id.to_string(),
+                pid,
+                log_path: format!("{}/log", state_dir),
+                state_dir,
+            }),
+            _ => Err(SshSessionError::Other(format!(
+                "Can not parse state of background process {}: {}",
+                id, output
+            ))),
+        }
+    }
+
+    pub async fn status(
+        &self,
+        session: &SshSession,
+        execute_timeout: Duration,
+    ) -> Result<SshBackgroundProcessStatus, SshSessionError> {
+        let command = format!(
+            "cd {} && if [ -f exit_code ]; then echo exited; cat exit_code; elif kill -0 {} 2>/dev/null; then echo running; else echo lost; fi",
+            shell_quote(&self.state_dir),
+            self.pid
+        );
+
+        let (output, exit_code) = session.execute_command(command, execute_timeout).await?;
+
+        if exit_code != 0 {
+            return Err(SshSessionError::BackgroundProcessNotFound(self.id.clone()));
+        }
+
+        let mut lines = output.lines();
+
+        match lines.next() {
+            Some("running") => Ok(SshBackgroundProcessStatus::Running),
+            Some("exited") => {
+                let exit_code = lines.next().and_then(|itm| itm.trim().parse().ok());
+                match exit_code {
+                    Some(exit_code) => Ok(SshBackgroundProcessStatus::Exited(exit_code)),
+                    // exit_code file is being written right now
+                    None => Ok(SshBackgroundProcessStatus::Running),
+                }
+            }
+            _ => Ok(SshBackgroundProcessStatus::Lost),
+        }
+    }
+
+    pub async fn tail_log(
+        &self,
+        session: &SshSession,
+        lines: usize,
+        execute_timeout: Duration,
+    ) -> Result<String, SshSessionError> {
+        let command = format!("tail -n {} {}", lines, shell_quote(&self.log_path));
+        let (output, _) = session.execute_command(command, execute_timeout).await?;
+        Ok(output)
+    }
+
+    // Signal is delivered to the whole process group, so children of the process get it as well.
+    // signal - name (TERM, KILL, HUP) or number
+    pub async fn signal(
+        &self,
+        session: &SshSession,
+        signal: &str,
+        execute_timeout: Duration,
+    ) -> Result<(), SshSessionError> {
+        let command = format!(
+            "kill -s {} -- -{} 2>/dev/null || kill -s {} {}",
+            shell_quote(signal),
+            self.pid,
+            shell_quote(signal),
+            self.pid
+        );
+
+        let (output, exit_code) = session.execute_command(command, execute_timeout).await?;
+
+        if exit_code != 0 {
+            return Err(SshSessionError::Other(format!(
+                "Can not send signal {} to background process {}. {}",
+                signal, self.id, output
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Polls status until the process is not running anymore
+    pub async fn wait(
+        &self,
+        session: &SshSession,
+        poll_interval: Duration,
+        wait_timeout: Duration,
+    ) -> Result<SshBackgroundProcessStatus, SshSessionError> {
+        let deadline = tokio::time::Instant::now() + wait_timeout;
+
+        loop {
+            let now = tokio::time::Instant::now();
+            if now >= deadline {
+                return Err(SshSessionError::Timeout);
+            }
+
+            let status = self.status(session, deadline - now).await?;
+
+            if status != SshBackgroundProcessStatus::Running {
+                return Ok(status);
+            }
+
+            tokio::time::sleep_until(deadline.min(tokio::time::Instant::now() + poll_interval))
+                .await;
+        }
+    }
+
+    // Removes the state directory together with the log
+    pub async fn remove_state(
+        &self,
+        session: &SshSession,
+        execute_timeout: Duration,
+    ) -> Result<(), SshSessionError> {
+        let command = format!("rm -rf {}", shell_quote(&self.state_dir));
+        session.execute_command(command, execute_timeout).await?;
+        Ok(())
+    }
+}
+
+impl SshSession {
+    // Starts the command detached from the session with setsid/nohup.
+    // stdout and stderr go to the log file in the state directory
+    pub async fn start_background_process(
+        &self,
+        command: impl AsRef<str>,
+        execute_timeout: Duration,
+    ) -> Result<SshBackgroundProcess, SshSessionError> {
+        let id = DateTimeAsMicroseconds::now().unix_microseconds.to_string();
+        let command = command.as_ref();
+
+        // Command runs in $HOME in a subshell; exit code is written to the path passed as $0
+        let script = format!("cd \"$HOME\" || exit 1\n( {}\n)\necho $? > \"$0\"", command);
+
+        let start_command = format!(
+            "mkdir -p ~/{dir}/{id} && cd ~/{dir}/{id} && printf '%s\\n' {command} > command && ($(command -v setsid) nohup sh -c {script} \"$PWD/exit_code\" > log 2>&1 < /dev/null & echo $! > pid) && pwd && cat pid",
+            dir = BACKGROUND_PROCESSES_DIR,
+            id = id,
+            command = shell_quote(command),
+            script = shell_quote(&script),
+        );
+
+        let (output, exit_code) = self.execute_command(start_command, execute_timeout).await?;
+
+        if exit_code != 0 {
+            return Err(SshSessionError::Other(format!(
+                "Can not start background process. Exit code: {}. Output: {}",
+                exit_code, output
+            )));
+        }
+
+        SshBackgroundProcess::from_output(id.as_str(), output.as_str())
+    }
+
+    // Restores the handle of the process started earlier, possibly by another session
+    pub async fn get_background_process(
+        &self,
+        id: &str,
+        execute_timeout: Duration,
+    ) -> Result<SshBackgroundProcess, SshSessionError> {
+        let command = format!(
+            "cd ~/{}/{} && pwd && cat pid",
+            BACKGROUND_PROCESSES_DIR,
+            shell_quote(id)
+        );
+
+        let (output, exit_code) = self.execute_command(command, execute_timeout).await?;
+
+        if exit_code != 0 {
+            return Err(SshSessionError::BackgroundProcessNotFound(id.to_string()));
+        }
+
+        SshBackgroundProcess::from_output(id, output.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SshBackgroundProcess;
+
+    #[test]
+    fn test_from_output() {
+        let process =
+            SshBackgroundProcess::from_output("123", "/home/user/.my-ssh/processes/123\n4567\n")
+                .unwrap();
+
+        assert_eq!(process.pid, 4567);
+        assert_eq!(process.state_dir, "/home/user/.my-ssh/processes/123");
+        assert_eq!(process.log_path, "/home/user/.my-ssh/processes/123/log");
+    }
+}
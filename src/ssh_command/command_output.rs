@@ -0,0 +1,18 @@
+// Exit code reported when the channel was closed before the remote process finished
+pub const INTERRUPTED_EXIT_CODE: i32 = -1;
+
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    // INTERRUPTED_EXIT_CODE when output is truncated with OutputLimitPolicy::Truncate
+    pub exit_code: i32,
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+}
+
+impl CommandOutput {
+    pub fn is_success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+use futures::{future::Either, AsyncReadExt};
+
+use crate::{SshAsyncChannel, SshSessionError};
+
+use super::{
+    CommandOutput, CommandOutputLimits, CommandOutputStream, OutputLimit, OutputLimitPolicy,
+    INTERRUPTED_EXIT_CODE,
+};
+
+const READ_BUFFER_SIZE: usize = 32 * 1024;
+
+enum CollectResult {
+    Continue,
+    Stop,
+    LimitExceeded(usize),
+}
+
+struct OutputCollector {
+    limit: Option<OutputLimit>,
+    data: VecDeque<u8>,
+    truncated: bool,
+    done: bool,
+}
+
+impl OutputCollector {
+    fn new(limit: Option<OutputLimit>) -> Self {
+        Self {
+            limit,
+            data: VecDeque::new(),
+            truncated: false,
+            done: false,
+        }
+    }
+
+    fn push(&mut self, chunk: &[u8]) -> CollectResult {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => {
+                self.data.extend(chunk);
+                return CollectResult::Continue;
+            }
+        };
+
+        if self.data.len() + chunk.len() <= limit.max_bytes {
+            self.data.extend(chunk);
+            return CollectResult::Continue;
+        }
+
+        match limit.policy {
+            OutputLimitPolicy::Truncate => {
+                let remains = limit.max_bytes - self.data.len();
+                self.data.extend(&chunk[..remains]);
+                self.truncated = true;
+                CollectResult::Stop
+            }
+            OutputLimitPolicy::KeepTail => {
+                self.data.extend(chunk);
+                let extra = self.data.len() - limit.max_bytes;
+                self.data.drain(..extra);
+                self.truncated = true;
+                CollectResult::Continue
+            }
+            OutputLimitPolicy::Fail => CollectResult::LimitExceeded(limit.max_bytes),
+        }
+    }
+
+    fn into_string(self) -> String {
+        let data: Vec<u8> = self.data.into();
+        String::from_utf8_lossy(&data).to_string()
+    }
+}
+
+// Reads stdout and stderr simultaneously so the remote side never stalls on a full stderr window.
+// When a limit stops reading, the channel is closed so the remote process does not keep streaming.
+// The remote side has not sent EOF at this point, so the close is not awaited and there is no exit status
+pub(crate) async fn read_command_output(
+    channel: &mut SshAsyncChannel,
+    limits: &CommandOutputLimits,
+) -> Result<CommandOutput, SshSessionError> {
+    let mut stdout_stream = channel.stream(0);
+    let mut stderr_stream = channel.stderr();
+
+    let mut stdout_buf = vec![0u8; READ_BUFFER_SIZE];
+    let mut stderr_buf = vec![0u8; READ_BUFFER_SIZE];
+
+    let mut stdout = OutputCollector::new(limits.stdout);
+    let mut stderr = OutputCollector::new(limits.stderr);
+
+    while !stdout.done || !stderr.done {
+        let (stream, result) = {
+            let stdout_read = async {
+                if stdout.done {
+                    futures::future::pending::<std::io::Result<usize>>().await
+                } else {
+                    stdout_stream.read(&mut stdout_buf).await
+                }
+            };
+
+            let stderr_read = async {
+                if stderr.done {
+                    futures::future::pending::<std::io::Result<usize>>().await
+                } else {
+                    stderr_stream.read(&mut stderr_buf).await
+                }
+            };
+
+            futures::pin_mut!(stdout_read, stderr_read);
+
+            match futures::future::select(stdout_read, stderr_read).await {
+                Either::Left((result, _)) => (CommandOutputStream::Stdout, result),
+                Either::Right((result, _)) => (CommandOutputStream::Stderr, result),
+            }
+        };
+
+        let size = result?;
+
+        let (collector, buf) = match stream {
+            CommandOutputStream::Stdout => (&mut stdout, &stdout_buf),
+            CommandOutputStream::Stderr => (&mut stderr, &stderr_buf),
+        };
+
+        if size == 0 {
+            collector.done = true;
+            continue;
+        }
+
+        match collector.push(&buf[..size]) {
+            CollectResult::Continue => {}
+            CollectResult::Stop => {
+                channel.close().await?;
+
+                return Ok(CommandOutput {
+                    stdout_truncated: stdout.truncated,
+                    stderr_truncated: stderr.truncated,
+                    stdout: stdout.into_string(),
+                    stderr: stderr.into_string(),
+                    exit_code: INTERRUPTED_EXIT_CODE,
+                });
+            }
+            CollectResult::LimitExceeded(limit) => {
+                channel.close().await?;
+                return Err(SshSessionError::OutputLimitExceeded { stream, limit });
+            }
+        }
+    }
+
+    channel.wait_close().await?;
+
+    Ok(CommandOutput {
+        stdout_truncated: stdout.truncated,
+        stderr_truncated: stderr.truncated,
+        stdout: stdout.into_string(),
+        stderr: stderr.into_string(),
+        exit_code: channel.exit_status()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CollectResult, OutputCollector};
+    use crate::OutputLimit;
+
+    #[test]
+    fn test_truncate() {
+        let mut collector = OutputCollector::new(Some(OutputLimit::truncate(5)));
+        assert!(matches!(collector.push(b"abc"), CollectResult::Continue));
+        assert!(matches!(collector.push(b"defg"), CollectResult::Stop));
+        assert!(collector.truncated);
+        assert_eq!(collector.into_string(), "abcde");
+    }
+
+    #[test]
+    fn test_keep_tail() {
+        let mut collector = OutputCollector::new(Some(OutputLimit::keep_tail(5)));
+        assert!(matches!(collector.push(b"abc"), CollectResult::Continue));
+        assert!(matches!(collector.push(b"defg"), CollectResult::Continue));
+        assert!(collector.truncated);
+        assert_eq!(collector.into_string(), "cdefg");
+    }
+
+    #[test]
+    fn test_fail() {
+        let mut collector = OutputCollector::new(Some(OutputLimit::fail(5)));
+        assert!(matches!(collector.push(b"abcde"), CollectResult::Continue));
+        assert!(matches!(
+            collector.push(b"f"),
+            CollectResult::LimitExceeded(5)
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use serde::de::DeserializeOwned;
+
+use crate::SshSessionError;
+
+use super::CommandOutput;
+
+impl CommandOutput {
+    pub fn parse_json<T: DeserializeOwned>(&self) -> Result<T, SshSessionError> {
+        self.check_exit_code()?;
+
+        serde_json::from_str(self.stdout.as_str()).map_err(|err| self.to_json_error(err))
+    }
+
+    // One JSON document per line. Empty lines are skipped
+    pub fn parse_json_lines<T: DeserializeOwned>(&self) -> Result<Vec<T>, SshSessionError> {
+        self.check_exit_code()?;
+
+        let mut result = Vec::new();
+
+        for line in self.stdout.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let item = serde_json::from_str(line).map_err(|err| self.to_json_error(err))?;
+            result.push(item);
+        }
+
+        Ok(result)
+    }
+
+    fn check_exit_code(&self) -> Result<(), SshSessionError> {
+        if self.exit_code == 0 {
+            return Ok(());
+        }
+
+        Err(SshSessionError::JsonCommandFailed {
+            exit_code: self.exit_code,
+            stderr: self.stderr.clone(),
+            deserialize_error: None,
+        })
+    }
+
+    fn to_json_error(&self, err: serde_json::Error) -> SshSessionError {
+        SshSessionError::JsonCommandFailed {
+            exit_code: self.exit_code,
+            stderr: self.stderr.clone(),
+            deserialize_error: Some(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CommandOutput, SshSessionError};
+
+    fn output(stdout: &str, exit_code: i32) -> CommandOutput {
+        CommandOutput {
+            stdout: stdout.to_string(),
+            stderr: "some warning".to_string(),
+            exit_code,
+            stdout_truncated: false,
+            stderr_truncated: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_json_lines() {
+        let result: Vec<u32> = output("1\n\n2\n3\n", 0).parse_json_lines().unwrap();
+        assert_eq!(result, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_non_zero_exit_code() {
+        let result: Result<u32, _> = output("1", 2).parse_json();
+
+        match result {
+            Err(SshSessionError::JsonCommandFailed {
+                exit_code,
+                stderr,
+                deserialize_error,
+            }) => {
+                assert_eq!(exit_code, 2);
+                assert_eq!(stderr, "some warning");
+                assert!(deserialize_error.is_none());
+            }
+            _ => panic!("JsonCommandFailed is expected"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_error() {
+        let result: Result<u32, _> = output("not a json", 0).parse_json();
+        assert!(matches!(
+            result,
+            Err(SshSessionError::JsonCommandFailed {
+                deserialize_error: Some(_),
+                ..
+            })
+        ));
+    }
+}
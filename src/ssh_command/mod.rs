@@ -0,0 +1,15 @@
+mod shell_quote;
+pub use shell_quote::*;
+mod ssh_command_builder;
+pub use ssh_command_builder::*;
+mod shell_command;
+pub use shell_command::*;
+mod command_output;
+pub use command_output::*;
+mod json_output;
+mod output_limit;
+pub use output_limit::*;
+mod command_output_reader;
+pub use command_output_reader::*;
+mod ssh_run_as;
+pub use ssh_run_as::*;
@@ -0,0 +1,50 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputLimitPolicy {
+    // Keep the first max_bytes, close the channel and flag the output as truncated
+    Truncate,
+    // Keep the last max_bytes. The stream is read to the end in bounded memory
+    KeepTail,
+    // Close the channel and return SshSessionError::OutputLimitExceeded
+    Fail,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OutputLimit {
+    pub max_bytes: usize,
+    pub policy: OutputLimitPolicy,
+}
+
+impl OutputLimit {
+    pub fn truncate(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            policy: OutputLimitPolicy::Truncate,
+        }
+    }
+
+    pub fn keep_tail(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            policy: OutputLimitPolicy::KeepTail,
+        }
+    }
+
+    pub fn fail(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            policy: OutputLimitPolicy::Fail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommandOutputLimits {
+    pub stdout: Option<OutputLimit>,
+    pub stderr: Option<OutputLimit>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandOutputStream {
+    Stdout,
+    Stderr,
+}
@@ -0,0 +1,216 @@
+use super::{shell_quote, SshCommand};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellCommandKind {
+    Simple,
+    Pipeline,
+    AndOr,
+    Sequence,
+}
+
+// Builds POSIX command lines from a program and its arguments.
+// Compositions keep the order they were built in: a.and_then(b).pipe(c) means { a && b; } | c
+#[derive(Debug, Clone)]
+pub struct ShellCommand {
+    command_line: String,
+    kind: ShellCommandKind,
+}
+
+impl ShellCommand {
+    pub fn new(program: &str) -> Self {
+        Self {
+            command_line: shell_quote(program),
+            kind: ShellCommandKind::Simple,
+        }
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.command_line.push(' ');
+        self.command_line.push_str(shell_quote(arg).as_str());
+        self
+    }
+
+    pub fn args<T: AsRef<str>>(mut self, args: impl IntoIterator<Item = T>) -> Self {
+        for arg in args {
+            self = self.arg(arg.as_ref());
+        }
+        self
+    }
+
+    // Argument is not quoted. Use it for globs or variables which must be expanded remotely
+    pub fn raw_arg(mut self, arg: &str) -> Self {
+        self.command_line.push(' ');
+        self.command_line.push_str(arg);
+        self
+    }
+
+    pub fn pipe(self, next: ShellCommand) -> Self {
+        let left = self.into_operand(|kind| kind == ShellCommandKind::Pipeline);
+        let right = next.into_operand(|kind| kind == ShellCommandKind::Pipeline);
+        Self::compose(left, " | ", right, ShellCommandKind::Pipeline)
+    }
+
+    // next is executed only if this command succeeds
+    pub fn and_then(self, next: ShellCommand) -> Self {
+        self.and_or(" && ", next)
+    }
+
+    // next is executed only if this command fails
+    pub fn or_else(self, next: ShellCommand) -> Self {
+        self.and_or(" || ", next)
+    }
+
+    // next is executed regardless of the result of this command
+    pub fn then(self, next: ShellCommand) -> Self {
+        let left = self.into_operand(|_| true);
+        let right = next.into_operand(|_| true);
+        Self::compose(left, "; ", right, ShellCommandKind::Sequence)
+    }
+
+    pub fn stdout_to(self, path: &str) -> Self {
+        self.redirect(">", path)
+    }
+
+    pub fn stdout_append_to(self, path: &str) -> Self {
+        self.redirect(">>", path)
+    }
+
+    pub fn stderr_to(self, path: &str) -> Self {
+        self.redirect("2>", path)
+    }
+
+    pub fn stderr_to_stdout(self) -> Self {
+        let mut result = self.group();
+        result.command_line.push_str(" 2>&1");
+        result
+    }
+
+    pub fn stdin_from(self, path: &str) -> Self {
+        self.redirect("<", path)
+    }
+
+    // Wraps the command into { ...; } so it is treated as a single command
+    pub fn group(self) -> Self {
+        if self.kind == ShellCommandKind::Simple {
+            return self;
+        }
+
+        Self {
+            command_line: format!("{{ {}; }}", self.command_line),
+            kind: ShellCommandKind::Simple,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.command_line.as_str()
+    }
+
+    fn and_or(self, operator: &str, next: ShellCommand) -> Self {
+        let left = self.into_operand(|kind| kind != ShellCommandKind::Sequence);
+        let right = next.into_operand(|kind| kind == ShellCommandKind::Pipeline);
+        Self::compose(left, operator, right, ShellCommandKind::AndOr)
+    }
+
+    fn redirect(self, operator: &str, path: &str) -> Self {
+        let mut result = self.group();
+        result.command_line.push(' ');
+        result.command_line.push_str(operator);
+        result.command_line.push(' ');
+        result.command_line.push_str(shell_quote(path).as_str());
+        result
+    }
+
+    // Operands of the same kind can be joined as they are. Everything else is grouped
+    fn into_operand(self, can_join: impl Fn(ShellCommandKind) -> bool) -> Self {
+        if self.kind == ShellCommandKind::Simple || can_join(self.kind) {
+            self
+        } else {
+            self.group()
+        }
+    }
+
+    fn compose(left: Self, operator: &str, right: Self, kind: ShellCommandKind) -> Self {
+        let mut command_line = String::with_capacity(
+            left.command_line.len() + operator.len() + right.command_line.len(),
+        );
+        command_line.push_str(left.command_line.as_str());
+        command_line.push_str(operator);
+        command_line.push_str(right.command_line.as_str());
+
+        Self { command_line, kind }
+    }
+}
+
+impl AsRef<str> for ShellCommand {
+    fn as_ref(&self) -> &str {
+        self.command_line.as_str()
+    }
+}
+
+impl std::fmt::Display for ShellCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.command_line.as_str())
+    }
+}
+
+impl From<ShellCommand> for String {
+    fn from(src: ShellCommand) -> Self {
+        src.command_line
+    }
+}
+
+impl From<ShellCommand> for SshCommand {
+    fn from(src: ShellCommand) -> Self {
+        SshCommand::from_command_line(src)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShellCommand;
+
+    #[test]
+    fn test_args_are_quoted() {
+        let command = ShellCommand::new("cat").arg("my file.txt").arg("it's");
+        assert_eq!(command.as_str(), "cat 'my file.txt' 'it'\\''s'");
+    }
+
+    #[test]
+    fn test_pipeline_with_redirections() {
+        let command = ShellCommand::new("grep")
+            .arg("error")
+            .stdin_from("/var/log/app log")
+            .pipe(ShellCommand::new("wc").arg("-l"))
+            .stdout_to("/tmp/count");
+
+        assert_eq!(
+            command.as_str(),
+            "{ grep error < '/var/log/app log' | wc -l; } > /tmp/count"
+        );
+    }
+
+    #[test]
+    fn test_chains_keep_build_order() {
+        let command = ShellCommand::new("mkdir")
+            .arg("-p")
+            .arg("/tmp/x")
+            .and_then(ShellCommand::new("cd").arg("/tmp/x"))
+            .pipe(ShellCommand::new("tee").arg("log"));
+
+        assert_eq!(
+            command.as_str(),
+            "{ mkdir -p /tmp/x && cd /tmp/x; } | tee log"
+        );
+
+        let command = ShellCommand::new("true")
+            .then(ShellCommand::new("false"))
+            .or_else(ShellCommand::new("echo").arg("failed"));
+
+        assert_eq!(command.as_str(), "{ true; false; } || echo failed");
+
+        let command =
+            ShellCommand::new("a").and_then(ShellCommand::new("b").or_else(ShellCommand::new("c")));
+
+        assert_eq!(command.as_str(), "a && { b || c; }");
+    }
+}
@@ -0,0 +1,82 @@
+// POSIX shell quoting. Values which contain only safe characters are kept as they are,
+// everything else is wrapped into single quotes
+pub fn shell_quote(value: &str) -> String {
+    if value.is_empty() {
+        return "''".to_string();
+    }
+
+    if value.chars().all(is_safe_char) {
+        return value.to_string();
+    }
+
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('\'');
+
+    for c in value.chars() {
+        if c == '\'' {
+            result.push_str("'\\''");
+        } else {
+            result.push(c);
+        }
+    }
+
+    result.push('\'');
+    result
+}
+
+// Same as shell_quote, but keeps leading ~/ unquoted so the remote shell expands the home directory
+pub fn shell_quote_path(path: &str) -> String {
+    if path == "~" {
+        return "~".to_string();
+    }
+
+    if let Some(rest) = path.strip_prefix("~/") {
+        if rest.is_empty() {
+            return "~/".to_string();
+        }
+        return format!("~/{}", shell_quote(rest));
+    }
+
+    shell_quote(path)
+}
+
+fn is_safe_char(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || c == '_'
+        || c == '-'
+        || c == '.'
+        || c == '/'
+        || c == ':'
+        || c == ','
+        || c == '+'
+        || c == '='
+        || c == '@'
+        || c == '%'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_values_are_not_quoted() {
+        assert_eq!(shell_quote("ls"), "ls");
+        assert_eq!(shell_quote("/var/log/app.log"), "/var/log/app.log");
+        assert_eq!(shell_quote("--name=value"), "--name=value");
+    }
+
+    #[test]
+    fn test_unsafe_values_are_quoted() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("my file.txt"), "'my file.txt'");
+        assert_eq!(shell_quote("$(rm -rf /)"), "'$(rm -rf /)'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_home_path() {
+        assert_eq!(shell_quote_path("~"), "~");
+        assert_eq!(shell_quote_path("~/my dir"), "~/'my dir'");
+        assert_eq!(shell_quote_path("/tmp/~x"), "'/tmp/~x'");
+    }
+}
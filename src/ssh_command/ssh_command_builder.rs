@@ -0,0 +1,251 @@
+use crate::SshSessionError;
+
+use super::{shell_quote, shell_quote_path, CommandOutputLimits, OutputLimit, SshRunAs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshCommandShellMode {
+    // Command line is sent to exec as it is
+    Exec,
+    // Command line is wrapped into `bash -lc` so profile-defined variables such as PATH apply
+    LoginShell,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshCommand {
+    command_line: String,
+    env: Vec<(String, String)>,
+    // First invalid variable name passed to env. Command is refused on execute
+    invalid_env_name: Option<String>,
+    working_dir: Option<String>,
+    umask: Option<u32>,
+    shell_mode: SshCommandShellMode,
+    run_as: Option<SshRunAs>,
+    output_limits: CommandOutputLimits,
+}
+
+impl SshCommand {
+    pub fn new(program: &str) -> Self {
+        Self::from_command_line(shell_quote(program))
+    }
+
+    // Command line is used as it is. Caller is responsible for quoting
+    pub fn from_command_line(command_line: impl Into<String>) -> Self {
+        Self {
+            command_line: command_line.into(),
+            env: Vec::new(),
+            invalid_env_name: None,
+            working_dir: None,
+            umask: None,
+            shell_mode: SshCommandShellMode::Exec,
+            run_as: None,
+            output_limits: CommandOutputLimits::default(),
+        }
+    }
+
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.command_line.push(' ');
+        self.command_line.push_str(shell_quote(arg).as_str());
+        self
+    }
+
+    pub fn args<T: AsRef<str>>(mut self, args: impl IntoIterator<Item = T>) -> Self {
+        for arg in args {
+            self = self.arg(arg.as_ref());
+        }
+        self
+    }
+
+    // Sent with the SSH setenv request. If server does not accept it - exported inline.
+    // Invalid name is not added; execution of the command fails instead
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        if !is_valid_env_name(name) {
+            if self.invalid_env_name.is_none() {
+                self.invalid_env_name = Some(name.to_string());
+            }
+            return self;
+        }
+
+        self.env.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn working_dir(mut self, working_dir: &str) -> Self {
+        self.working_dir = Some(working_dir.to_string());
+        self
+    }
+
+    pub fn umask(mut self, umask: u32) -> Self {
+        self.umask = Some(umask);
+        self
+    }
+
+    pub fn shell_mode(mut self, shell_mode: SshCommandShellMode) -> Self {
+        self.shell_mode = shell_mode;
+        self
+    }
+
+    pub fn login_shell(self) -> Self {
+        self.shell_mode(SshCommandShellMode::LoginShell)
+    }
+
+    // Executes the command on behalf of another user with sudo or su
+    pub fn run_as(mut self, run_as: SshRunAs) -> Self {
+        self.run_as = Some(run_as);
+        self
+    }
+
+    pub fn max_stdout(mut self, limit: OutputLimit) -> Self {
+        self.output_limits.stdout = Some(limit);
+        self
+    }
+
+    pub fn max_stderr(mut self, limit: OutputLimit) -> Self {
+        self.output_limits.stderr = Some(limit);
+        self
+    }
+
+    pub fn get_command_line(&self) -> &str {
+        self.command_line.as_str()
+    }
+
+    pub fn get_env(&self) -> &[(String, String)] {
+        self.env.as_slice()
+    }
+
+    pub fn get_shell_mode(&self) -> SshCommandShellMode {
+        self.shell_mode
+    }
+
+    pub fn get_run_as(&self) -> Option<&SshRunAs> {
+        self.run_as.as_ref()
+    }
+
+    pub fn get_output_limits(&self) -> &CommandOutputLimits {
+        &self.output_limits
+    }
+
+    pub fn validate(&self) -> Result<(), SshSessionError> {
+        if let Some(name) = self.invalid_env_name.as_ref() {
+            return Err(SshSessionError::Other(format!(
+                "Invalid environment variable name: '{}'",
+                name
+            )));
+        }
+
+        Ok(())
+    }
+
+    // Builds the line to be sent to exec. inline_env - variables server refused to accept with setenv
+    pub fn build_command_line(&self, inline_env: &[(String, String)]) -> String {
+        let mut result = String::new();
+
+        if let Some(umask) = self.umask {
+            result.push_str(format!("umask {:03o} && ", umask).as_str());
+        }
+
+        if let Some(working_dir) = self.working_dir.as_ref() {
+            result.push_str("cd ");
+            result.push_str(shell_quote_path(working_dir).as_str());
+            result.push_str(" && ");
+        }
+
+        for (name, value) in inline_env {
+            result.push_str("export ");
+            result.push_str(name);
+            result.push('=');
+            result.push_str(shell_quote(value).as_str());
+            result.push_str(" && ");
+        }
+
+        if result.is_empty() {
+            result.push_str(self.command_line.as_str());
+        } else {
+            // Grouping keeps `a || b` inside the command from skipping the prefix checks
+            result.push_str("{ ");
+            result.push_str(self.command_line.as_str());
+            result.push_str("\n}");
+        }
+
+        if let Some(run_as) = self.run_as.as_ref() {
+            return run_as
+                .wrap_command_line(self.shell_mode == SshCommandShellMode::LoginShell, &result);
+        }
+
+        match self.shell_mode {
+            SshCommandShellMode::Exec => result,
+            SshCommandShellMode::LoginShell => format!("bash -lc {}", shell_quote(&result)),
+        }
+    }
+}
+
+fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_command() {
+        let command = SshCommand::new("ls").arg("-la").arg("my dir");
+        assert_eq!(command.build_command_line(&[]), "ls -la 'my dir'");
+    }
+
+    #[test]
+    fn test_command_with_prefixes() {
+        let command = SshCommand::new("make")
+            .working_dir("~/src/my app")
+            .umask(0o022)
+            .env("FOO", "bar baz");
+
+        let inline_env = command.get_env().to_vec();
+
+        assert_eq!(
+            command.build_command_line(&inline_env),
+            "umask 022 && cd ~/'src/my app' && export FOO='bar baz' && { make\n}"
+        );
+    }
+
+    #[test]
+    fn test_run_as() {
+        let command = SshCommand::new("whoami")
+            .env("FOO", "bar")
+            .run_as(SshRunAs::sudo("postgres"));
+
+        let inline_env = command.get_env().to_vec();
+
+        assert_eq!(
+            command.build_command_line(&inline_env),
+            "sudo -n -u postgres -- sh -c 'export FOO=bar && { whoami\n}'"
+        );
+    }
+
+    #[test]
+    fn test_login_shell() {
+        let command = SshCommand::new("echo").arg("it's").login_shell();
+        assert_eq!(
+            command.build_command_line(&[]),
+            "bash -lc 'echo '\\''it'\\''\\'\\'''\\''s'\\'''"
+        );
+    }
+
+    #[test]
+    fn test_invalid_env_name_is_reported_on_validate() {
+        let command = SshCommand::new("env").env("FOO", "1").env("BAD NAME", "2");
+
+        assert!(matches!(
+            command.validate(),
+            Err(crate::SshSessionError::Other(_))
+        ));
+        assert_eq!(command.get_env().len(), 1);
+        assert!(SshCommand::new("env").env("FOO", "1").validate().is_ok());
+    }
+}
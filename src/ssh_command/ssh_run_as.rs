@@ -0,0 +1,379 @@
+use std::time::Duration;
+
+use regex::Regex;
+
+use crate::{ExpectMatch, SshAsyncChannel, SshExpectSession, SshSessionError};
+
+use super::{shell_quote, CommandOutput};
+
+// Prompt we ask sudo to print, so it can not be confused with the output of the command
+pub const SUDO_PASSWORD_PROMPT: &str = "[my-ssh-sudo-password]:";
+
+// sudo refuses right after the prompt or the password, so output of the command is never matched
+const NOT_ALLOWED_PATTERN: &str = concat!(
+    r"\A\s*(?:sudo: )?(?:Sorry, user \S+ (?:is not allowed to execute|may not run sudo)",
+    r"|\S+ is not in the sudoers file)"
+);
+const WRONG_PASSWORD_PATTERN: &str =
+    r"\A\s*(?:Sorry, try again|su: Authentication failure|su: incorrect password)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshRunAsMethod {
+    Sudo,
+    Su,
+}
+
+#[derive(Clone)]
+pub struct SshRunAs {
+    pub user: String,
+    pub method: SshRunAsMethod,
+    pub password: Option<String>,
+}
+
+impl SshRunAs {
+    pub fn sudo(user: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            method: SshRunAsMethod::Sudo,
+            password: None,
+        }
+    }
+
+    pub fn su(user: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            method: SshRunAsMethod::Su,
+            password: None,
+        }
+    }
+
+    // Password is typed on the prompt through a PTY and never gets to the command line
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    // payload - command line to be executed on behalf of the user
+    pub fn wrap_command_line(&self, login_shell: bool, payload: &str) -> String {
+        let mut result = String::new();
+
+        match self.method {
+            SshRunAsMethod::Sudo => {
+                result.push_str("sudo ");
+                if self.password.is_some() {
+                    result.push_str("-p ");
+                    result.push_str(shell_quote(SUDO_PASSWORD_PROMPT).as_str());
+                } else {
+                    // Without password we never let sudo wait for the prompt nobody answers
+                    result.push_str("-n");
+                }
+                result.push_str(" -u ");
+                result.push_str(shell_quote(&self.user).as_str());
+
+                if login_shell {
+                    result.push_str(" -- bash -lc ");
+                } else {
+                    result.push_str(" -- sh -c ");
+                }
+            }
+            SshRunAsMethod::Su => {
+                if login_shell {
+                    result.push_str("su -l -s /bin/bash -c ");
+                } else {
+                    result.push_str("su -s /bin/sh -c ");
+                }
+            }
+        }
+
+        result.push_str(shell_quote(payload).as_str());
+
+        if self.method == SshRunAsMethod::Su {
+            result.push(' ');
+            result.push_str(shell_quote(&self.user).as_str());
+        }
+
+        result
+    }
+}
+
+impl std::fmt::Debug for SshRunAs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SshRunAs")
+            .field("user", &self.user)
+            .field("method", &self.method)
+            .field("password", &self.password.as_ref().map(|_| "********"))
+            .finish()
+    }
+}
+
+// Converts sudo/su complaints of the command executed without PTY into typed errors
+pub(crate) fn check_run_as_output(output: CommandOutput) -> Result<CommandOutput, SshSessionError> {
+    if output.exit_code == 0 {
+        return Ok(output);
+    }
+
+    if output.stderr.contains("a password is required")
+        || output.stderr.contains("must be run from a terminal")
+    {
+        return Err(SshSessionError::RunAsPasswordRequired);
+    }
+
+    if Regex::new(NOT_ALLOWED_PATTERN)
+        .unwrap()
+        .is_match(output.stderr.as_str())
+    {
+        return Err(SshSessionError::RunAsNotAllowed(output.stderr));
+    }
+
+    Ok(output)
+}
+
+// Runs the command line inside PTY and answers the password prompt.
+// PTY merges stderr into stdout, so CommandOutput::stderr is always empty and output limits do not apply here
+pub(crate) async fn execute_with_password_prompt(
+    channel: SshAsyncChannel,
+    method: SshRunAsMethod,
+    password: &str,
+    timeout: Duration,
+) -> Result<CommandOutput, SshSessionError> {
+    let mut expect_session = SshExpectSession::new(channel);
+
+    match expect_session
+        .expect(&prompt_pattern(method), timeout)
+        .await
+    {
+        Ok(found) => {
+            if found.groups[1].is_some() {
+                let message = format!("{}{}", found.before, found.matched);
+                let _ = expect_session.close().await;
+                return Err(SshSessionError::RunAsNotAllowed(message));
+            }
+        }
+        // Command has finished without asking for password (NOPASSWD or cached credentials)
+        Err(SshSessionError::ExpectEof(output)) => {
+            let exit_code = expect_session.close().await?;
+            return Ok(CommandOutput {
+                stdout: normalize_pty_output(output.as_str()),
+                stderr: String::new(),
+                exit_code,
+                stdout_truncated: false,
+                stderr_truncated: false,
+            });
+        }
+        Err(err) => {
+            let _ = expect_session.close().await;
+            return Err(err);
+        }
+    }
+
+    expect_session.send_secret_line(password).await?;
+
+    match expect_session
+        .expect(&after_password_pattern(), timeout)
+        .await
+    {
+        Ok(found) => {
+            let _ = expect_session.close().await;
+            Err(classify_after_password(found))
+        }
+        Err(SshSessionError::ExpectEof(output)) => {
+            let exit_code = expect_session.close().await?;
+            Ok(CommandOutput {
+                stdout: normalize_pty_output(output.trim_start_matches(['\r', '\n'])),
+                stderr: String::new(),
+                exit_code,
+                stdout_truncated: false,
+                stderr_truncated: false,
+            })
+        }
+        Err(err) => {
+            let _ = expect_session.close().await;
+            Err(err)
+        }
+    }
+}
+
+// sudo is always started with our own prompt, so a command printing "Password:" never gets the password.
+// su prompt can not be changed and is localized, so any line ending with "password:" is taken
+fn prompt_pattern(method: SshRunAsMethod) -> Regex {
+    let prompt = match method {
+        SshRunAsMethod::Sudo => regex::escape(SUDO_PASSWORD_PROMPT),
+        SshRunAsMethod::Su => r"(?i:password:\s*\z)".to_string(),
+    };
+
+    let pattern = format!(r"({})|({})", prompt, NOT_ALLOWED_PATTERN);
+    Regex::new(pattern.as_str()).unwrap()
+}
+
+// sudo checks the sudoers policy only after the password is typed, so "not allowed" can come here as well
+fn after_password_pattern() -> Regex {
+    let pattern = format!(
+        r"({}|{})|({})",
+        regex::escape(SUDO_PASSWORD_PROMPT),
+        WRONG_PASSWORD_PATTERN,
+        NOT_ALLOWED_PATTERN
+    );
+
+    Regex::new(pattern.as_str()).unwrap()
+}
+
+fn classify_after_password(found: ExpectMatch) -> SshSessionError {
+    if found.groups[1].is_some() {
+        return SshSessionError::RunAsNotAllowed(format!("{}{}", found.before, found.matched));
+    }
+
+    SshSessionError::RunAsWrongPassword
+}
+
+fn normalize_pty_output(output: &str) -> String {
+    output.replace("\r\n", "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        after_password_pattern, check_run_as_output, classify_after_password, prompt_pattern,
+        SshRunAs, SshRunAsMethod, SUDO_PASSWORD_PROMPT,
+    };
+    use crate::{CommandOutput, ExpectMatch, SshSessionError};
+
+    fn failed_output(stderr: &str) -> CommandOutput {
+        CommandOutput {
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+            exit_code: 1,
+            stdout_truncated: false,
+            stderr_truncated: false,
+        }
+    }
+
+    fn match_after_password(received: &str) -> Option<SshSessionError> {
+        let captures = after_password_pattern().captures(received)?;
+        let whole = captures.get(0).unwrap();
+
+        let found = ExpectMatch {
+            before: received[..whole.start()].to_string(),
+            matched: whole.as_str().to_string(),
+            groups: captures
+                .iter()
+                .skip(1)
+                .map(|group| group.map(|itm| itm.as_str().to_string()))
+                .collect(),
+        };
+
+        Some(classify_after_password(found))
+    }
+
+    #[test]
+    fn test_sudo_without_password_never_prompts() {
+        let run_as = SshRunAs::sudo("postgres");
+        assert_eq!(
+            run_as.wrap_command_line(false, "psql -c 'select 1'"),
+            "sudo -n -u postgres -- sh -c 'psql -c '\\''select 1'\\'''"
+        );
+    }
+
+    #[test]
+    fn test_su() {
+        let run_as = SshRunAs::su("app");
+        assert_eq!(
+            run_as.wrap_command_line(true, "id"),
+            "su -l -s /bin/bash -c id app"
+        );
+    }
+
+    #[test]
+    fn test_password_is_not_in_command_line() {
+        let run_as = SshRunAs::sudo("root").with_password("secret");
+        let command_line = run_as.wrap_command_line(false, "id");
+        assert!(!command_line.contains("secret"));
+        assert!(!format!("{:?}", run_as).contains("secret"));
+    }
+
+    #[test]
+    fn test_sudo_answers_only_own_prompt() {
+        let sudo = prompt_pattern(SshRunAsMethod::Sudo);
+        assert!(sudo.is_match(SUDO_PASSWORD_PROMPT));
+        // NOPASSWD sudo runs the command right away; its prompts are not answered
+        assert!(!sudo.is_match("Enter password: "));
+
+        let su = prompt_pattern(SshRunAsMethod::Su);
+        assert!(su.is_match("Password: "));
+        assert!(!su.is_match("Password: ok\r\n"));
+    }
+
+    #[test]
+    fn test_check_run_as_output() {
+        assert!(check_run_as_output(CommandOutput {
+            exit_code: 0,
+            ..failed_output("")
+        })
+        .is_ok());
+
+        assert!(matches!(
+            check_run_as_output(failed_output("sudo: a password is required\n")),
+            Err(SshSessionError::RunAsPasswordRequired)
+        ));
+
+        assert!(matches!(
+            check_run_as_output(failed_output(
+                "app is not in the sudoers file.  This incident will be reported.\n"
+            )),
+            Err(SshSessionError::RunAsNotAllowed(_))
+        ));
+
+        // Failure of the command itself is not an error of sudo
+        let output = check_run_as_output(failed_output("psql: connection refused\n")).unwrap();
+        assert_eq!(output.exit_code, 1);
+    }
+
+    #[test]
+    fn test_not_allowed_after_password() {
+        let result = match_after_password(
+            "\r\nSorry, user app is not allowed to execute '/bin/sh -c id' as root on host.\r\n",
+        );
+
+        match result {
+            Some(SshSessionError::RunAsNotAllowed(message)) => {
+                assert!(message.contains("is not allowed to execute"))
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        assert!(matches!(
+            match_after_password("\r\napp is not in the sudoers file.\r\n"),
+            Some(SshSessionError::RunAsNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_wrong_password_after_password() {
+        assert!(matches!(
+            match_after_password("\r\nSorry, try again.\r\n"),
+            Some(SshSessionError::RunAsWrongPassword)
+        ));
+
+        assert!(matches!(
+            match_after_password(format!("\r\n{}", SUDO_PASSWORD_PROMPT).as_str()),
+            Some(SshSessionError::RunAsWrongPassword)
+        ));
+
+        // Output of the command is not classified
+        assert!(match_after_password("\r\nuid=0(root) gid=0(root)\r\n").is_none());
+    }
+
+    #[test]
+    fn test_output_mentioning_sudoers_is_not_refusal() {
+        let output = concat!(
+            "\r\nMay  1 10:00:00 host sudo: Sorry, user bob may not run sudo on host.\r\n",
+            "May  1 10:00:05 host sudo: bob is not in the sudoers file\r\n",
+        );
+        assert!(match_after_password(output).is_none());
+
+        let output = CommandOutput {
+            stderr: "grep: warning: bob is not in the sudoers file\n".to_string(),
+            ..failed_output("")
+        };
+        assert_eq!(check_run_as_output(output).unwrap().exit_code, 1);
+    }
+}
@@ -0,0 +1,310 @@
+use std::time::Duration;
+
+use futures::{AsyncReadExt, AsyncWriteExt};
+use regex::Regex;
+
+use crate::{SshAsyncChannel, SshSessionError, INTERRUPTED_EXIT_CODE};
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub enum SshTranscriptEntry {
+    Received(String),
+    Sent(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ExpectMatch {
+    // Everything received before the match
+    pub before: String,
+    pub matched: String,
+    // Capture groups of the pattern. Index 0 is the first explicit group
+    pub groups: Vec<Option<String>>,
+}
+
+// Scripting layer over a PTY channel for appliances which only offer interactive CLIs
+pub struct SshExpectSession {
+    channel: SshAsyncChannel,
+    received: ExpectBuffer,
+    eof: bool,
+}
+
+impl SshExpectSession {
+    pub fn new(channel: SshAsyncChannel) -> Self {
+        Self {
+            channel,
+            received: ExpectBuffer::default(),
+            eof: false,
+        }
+    }
+
+    pub async fn expect(
+        &mut self,
+        pattern: &Regex,
+        timeout: Duration,
+    ) -> Result<ExpectMatch, SshSessionError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            if let Some(result) = self.received.try_match(pattern) {
+                return Ok(result);
+            }
+
+            if self.eof {
+                return Err(SshSessionError::ExpectEof(self.received.buffer.clone()));
+            }
+
+            let mut buf = [0u8; READ_BUFFER_SIZE];
+            let result = tokio::time::timeout_at(deadline, self.channel.read(&mut buf)).await;
+
+            if result.is_err() {
+                return Err(SshSessionError::ExpectTimeout(self.received.buffer.clone()));
+            }
+
+            let size = result.unwrap()?;
+
+            if size == 0 {
+                self.eof = true;
+                continue;
+            }
+
+            self.received.append(&buf[..size]);
+        }
+    }
+
+    pub async fn expect_text(
+        &mut self,
+        text: &str,
+        timeout: Duration,
+    ) -> Result<ExpectMatch, SshSessionError> {
+        let pattern = Regex::new(regex::escape(text).as_str()).unwrap();
+        self.expect(&pattern, timeout).await
+    }
+
+    // Reads everything until the remote side closes the channel
+    pub async fn read_to_end(&mut self, timeout: Duration) -> Result<String, SshSessionError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        while !self.eof {
+            let mut buf = [0u8; READ_BUFFER_SIZE];
+            let result = tokio::time::timeout_at(deadline, self.channel.read(&mut buf)).await;
+
+            if result.is_err() {
+                return Err(SshSessionError::ExpectTimeout(self.received.buffer.clone()));
+            }
+
+            let size = result.unwrap()?;
+
+            if size == 0 {
+                self.eof = true;
+            } else {
+                self.received.append(&buf[..size]);
+            }
+        }
+
+        Ok(std::mem::take(&mut self.received.buffer))
+    }
+
+    pub async fn send(&mut self, data: &str) -> Result<(), SshSessionError> {
+        self.channel.write_all(data.as_bytes()).await?;
+        self.channel.flush().await?;
+        self.received
+            .transcript
+            .push(SshTranscriptEntry::Sent(data.to_string()));
+        Ok(())
+    }
+
+    pub async fn send_line(&mut self, line: &str) -> Result<(), SshSessionError> {
+        let mut data = String::with_capacity(line.len() + 1);
+        data.push_str(line);
+        data.push('\n');
+        self.send(data.as_str()).await
+    }
+
+    // Same as send_line, but the content never gets to the transcript
+    pub async fn send_secret_line(&mut self, secret: &str) -> Result<(), SshSessionError> {
+        self.channel.write_all(secret.as_bytes()).await?;
+        self.channel.write_all(b"\n").await?;
+        self.channel.flush().await?;
+        self.received
+            .transcript
+            .push(SshTranscriptEntry::Sent("********\n".to_string()));
+        Ok(())
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    // Data received but not consumed by expect yet
+    pub fn get_buffer(&self) -> &str {
+        self.received.buffer.as_str()
+    }
+
+    pub fn get_transcript(&self) -> &[SshTranscriptEntry] {
+        self.received.transcript.as_slice()
+    }
+
+    pub fn transcript_to_string(&self) -> String {
+        let mut result = String::new();
+        for entry in self.received.transcript.iter() {
+            match entry {
+                SshTranscriptEntry::Received(data) => {
+                    result.push_str("<< ");
+                    result.push_str(data);
+                }
+                SshTranscriptEntry::Sent(data) => {
+                    result.push_str(">> ");
+                    result.push_str(data);
+                }
+            }
+
+            if !result.ends_with('\n') {
+                result.push('\n');
+            }
+        }
+
+        result
+    }
+
+    // Closes the channel and returns the exit status of the remote process.
+    // If the remote side has not sent EOF yet (a live shell), the process is not waited for
+    // and INTERRUPTED_EXIT_CODE is returned
+    pub async fn close(mut self) -> Result<i32, SshSessionError> {
+        self.channel.send_eof().await?;
+        self.channel.close().await?;
+
+        if !self.eof {
+            return Ok(INTERRUPTED_EXIT_CODE);
+        }
+
+        self.channel.wait_close().await?;
+        Ok(self.channel.exit_status()?)
+    }
+}
+
+// Received data and transcript. Kept apart from the channel, so matching can be tested without a connection
+#[derive(Default)]
+struct ExpectBuffer {
+    buffer: String,
+    not_decoded: Vec<u8>,
+    transcript: Vec<SshTranscriptEntry>,
+}
+
+impl ExpectBuffer {
+    fn append(&mut self, data: &[u8]) {
+        self.not_decoded.extend_from_slice(data);
+
+        // Multibyte characters can be split between two reads
+        let valid_up_to = match std::str::from_utf8(&self.not_decoded) {
+            Ok(_) => self.not_decoded.len(),
+            Err(err) => {
+                if err.error_len().is_some() {
+                    self.not_decoded.len()
+                } else {
+                    err.valid_up_to()
+                }
+            }
+        };
+
+        if valid_up_to == 0 {
+            return;
+        }
+
+        let decoded = String::from_utf8_lossy(&self.not_decoded[..valid_up_to]).to_string();
+        self.not_decoded.drain(..valid_up_to);
+
+        self.buffer.push_str(decoded.as_str());
+        self.transcript.push(SshTranscriptEntry::Received(decoded));
+    }
+
+    fn try_match(&mut self, pattern: &Regex) -> Option<ExpectMatch> {
+        let (result, end) = {
+            let captures = pattern.captures(self.buffer.as_str())?;
+            let whole = captures.get(0)?;
+
+            let groups = captures
+                .iter()
+                .skip(1)
+                .map(|group| group.map(|itm| itm.as_str().to_string()))
+                .collect();
+
+            let result = ExpectMatch {
+                before: self.buffer[..whole.start()].to_string(),
+                matched: whole.as_str().to_string(),
+                groups,
+            };
+
+            (result, whole.end())
+        };
+
+        self.buffer.drain(..end);
+
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{ExpectBuffer, SshTranscriptEntry};
+
+    #[test]
+    fn test_try_match() {
+        let mut received = ExpectBuffer::default();
+        received.append(b"Last login: today\r\nrouter(config)# rest");
+
+        let pattern = Regex::new(r"(\w+)\((\w+)\)# |(>) ").unwrap();
+        let found = received.try_match(&pattern).unwrap();
+
+        assert_eq!(found.before, "Last login: today\r\n");
+        assert_eq!(found.matched, "router(config)# ");
+        assert_eq!(
+            found.groups,
+            vec![Some("router".to_string()), Some("config".to_string()), None]
+        );
+
+        // Matched text is consumed, the rest waits for the next expect
+        assert_eq!(received.buffer, "rest");
+        assert!(received.try_match(&pattern).is_none());
+        assert_eq!(received.buffer, "rest");
+    }
+
+    #[test]
+    fn test_multibyte_split_between_reads() {
+        let text = "Привет €";
+        let bytes = text.as_bytes();
+        let mut received = ExpectBuffer::default();
+
+        // Cut inside of the second byte of 'р' and inside of '€'
+        received.append(&bytes[..3]);
+        assert_eq!(received.buffer, "П");
+
+        received.append(&bytes[3..bytes.len() - 1]);
+        assert_eq!(received.buffer, "Привет ");
+
+        received.append(&bytes[bytes.len() - 1..]);
+        assert_eq!(received.buffer, text);
+        assert!(received.not_decoded.is_empty());
+
+        let transcript: Vec<_> = received
+            .transcript
+            .iter()
+            .map(|entry| match entry {
+                SshTranscriptEntry::Received(data) => data.as_str(),
+                SshTranscriptEntry::Sent(data) => data.as_str(),
+            })
+            .collect();
+        assert_eq!(transcript, vec!["П", "ривет ", "€"]);
+    }
+
+    #[test]
+    fn test_invalid_bytes_are_not_kept() {
+        let mut received = ExpectBuffer::default();
+        received.append(b"a\xffb");
+
+        assert_eq!(received.buffer, "a\u{fffd}b");
+        assert!(received.not_decoded.is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::future::Either;
+use tokio::sync::{mpsc, watch, Semaphore};
+
+use crate::{CommandOutput, SshCommand, SshCredentials, SshSessionError, SshSessionsPool};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshFanOutErrorMode {
+    ContinueOnError,
+    // First failed host cancels the running hosts. Hosts which were not started are reported as Cancelled.
+    // Cancelling a running host closes its exec channel, but a remote command which is already running
+    // is not terminated: SSH server does not signal a process without PTY when its channel is closed
+    FailFast,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshFanOutOptions {
+    pub max_concurrency: usize,
+    // Covers connecting, authentication and command execution of one host
+    pub host_timeout: Duration,
+    pub error_mode: SshFanOutErrorMode,
+}
+
+impl Default for SshFanOutOptions {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 16,
+            host_timeout: Duration::from_secs(30),
+            error_mode: SshFanOutErrorMode::ContinueOnError,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SshFanOutHostResult {
+    pub credentials: Arc<SshCredentials>,
+    pub result: Result<CommandOutput, SshSessionError>,
+}
+
+impl SshSessionsPool {
+    // Runs the command on every host using pooled sessions.
+    // Results are delivered as hosts finish; receiver is closed when all the hosts are done
+    pub fn execute_on_hosts(
+        self: &Arc<Self>,
+        hosts: Vec<Arc<SshCredentials>>,
+        command: SshCommand,
+        options: SshFanOutOptions,
+    ) -> mpsc::Receiver<SshFanOutHostResult> {
+        let (sender, receiver) = mpsc::channel(hosts.len().max(1));
+
+        tokio::spawn(fan_out_loop(
+            self.clone(),
+            hosts,
+            Arc::new(command),
+            options,
+            sender,
+        ));
+
+        receiver
+    }
+}
+
+async fn fan_out_loop(
+    pool: Arc<SshSessionsPool>,
+    hosts: Vec<Arc<SshCredentials>>,
+    command: Arc<SshCommand>,
+    options: SshFanOutOptions,
+    sender: mpsc::Sender<SshFanOutHostResult>,
+) {
+    let semaphore = Arc::new(Semaphore::new(options.max_concurrency.max(1)));
+    let (cancel_sender, cancel_receiver) = watch::channel(false);
+    let cancel_sender = Arc::new(cancel_sender);
+
+    for credentials in hosts {
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+
+        if *cancel_receiver.borrow() {
+            let _ = sender
+                .send(SshFanOutHostResult {
+                    credentials,
+                    result: Err(SshSessionError::Cancelled),
+                })
+                .await;
+            continue;
+        }
+
+        let pool = pool.clone();
+        let command = command.clone();
+        let options = options.clone();
+        let sender = sender.clone();
+        let cancel_sender = cancel_sender.clone();
+        let cancel_receiver = cancel_receiver.clone();
+
+        tokio::spawn(async move {
+            let _permit = permit;
+
+            let execute = execute_on_host(&pool, &credentials, &command, options.host_timeout);
+            let cancelled = wait_cancelled(cancel_receiver);
+            futures::pin_mut!(execute, cancelled);
+
+            // Dropping the execute future drops the channel, and libssh2 closes a channel when it is freed.
+            // The pooled session stays connected
+            let result = match futures::future::select(execute, cancelled).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Err(SshSessionError::Cancelled),
+            };
+
+            if result.is_err() && options.error_mode == SshFanOutErrorMode::FailFast {
+                cancel_sender.send_replace(true);
+            }
+
+            let _ = sender
+                .send(SshFanOutHostResult {
+                    credentials,
+                    result,
+                })
+                .await;
+        });
+    }
+}
+
+async fn execute_on_host(
+    pool: &SshSessionsPool,
+    credentials: &Arc<SshCredentials>,
+    command: &SshCommand,
+    host_timeout: Duration,
+) -> Result<CommandOutput, SshSessionError> {
+    let future = async {
+        let session = pool.get_or_create(credentials).await;
+        session.execute(command, host_timeout).await
+    };
+
+    match tokio::time::timeout(host_timeout, future).await {
+        Ok(result) => result,
+        Err(_) => Err(SshSessionError::Timeout),
+    }
+}
+
+async fn wait_cancelled(mut cancel_receiver: watch::Receiver<bool>) {
+    while !*cancel_receiver.borrow() {
+        if cancel_receiver.changed().await.is_err() {
+            futures::future::pending::<()>().await;
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use futures::{future::Either, AsyncReadExt, AsyncWriteExt};
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+use crate::{CommandOutput, SshAsyncChannel, SshSessionError};
+
+const READ_BUFFER_SIZE: usize = 32 * 1024;
+
+// Long-lived `sh` channel which runs commands one by one. Every command is followed by
+// sentinel markers on stdout and stderr, so output and exit code of each command are separated
+// without opening a new channel
+pub struct SshPersistentShell {
+    channel: SshAsyncChannel,
+    id: i64,
+    command_no: u64,
+}
+
+impl SshPersistentShell {
+    pub fn new(channel: SshAsyncChannel) -> Self {
+        Self {
+            channel,
+            id: DateTimeAsMicroseconds::now().unix_microseconds,
+            command_no: 0,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.channel.eof()
+    }
+
+    pub async fn execute(&mut self, command: &str) -> Result<CommandOutput, SshSessionError> {
+        self.command_no += 1;
+        let marker = format!("__MY_SSH_{}_{}__", self.id, self.command_no);
+
+        // stdin is detached so the command can not consume the lines of the next commands
+        let script = format!(
+            "{{ {}\n}} </dev/null\nprintf '\\n{}:%d\\n' \"$?\"\nprintf '\\n{}\\n' >&2\n",
+            command, marker, marker
+        );
+
+        self.channel.write_all(script.as_bytes()).await?;
+        self.channel.flush().await?;
+
+        let stdout_marker = format!("\n{}:", marker);
+        let stderr_marker = format!("\n{}\n", marker);
+
+        let mut stdout_stream = self.channel.stream(0);
+        let mut stderr_stream = self.channel.stderr();
+
+        let mut stdout_buf = vec![0u8; READ_BUFFER_SIZE];
+        let mut stderr_buf = vec![0u8; READ_BUFFER_SIZE];
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+
+        let mut exit_code = None;
+        let mut stderr_end = None;
+
+        while exit_code.is_none() || stderr_end.is_none() {
+            let (is_stdout, result) = {
+                let stdout_read = async {
+                    if exit_code.is_some() {
+                        futures::future::pending::<std::io::Result<usize>>().await
+                    } else {
+                        stdout_stream.read(&mut stdout_buf).await
+                    }
+                };
+
+                let stderr_read = async {
+                    if stderr_end.is_some() {
+                        futures::future::pending::<std::io::Result<usize>>().await
+                    } else {
+                        stderr_stream.read(&mut stderr_buf).await
+                    }
+                };
+
+                futures::pin_mut!(stdout_read, stderr_read);
+
+                match futures::future::select(stdout_read, stderr_read).await {
+                    Either::Left((result, _)) => (true, result),
+                    Either::Right((result, _)) => (false, result),
+                }
+            };
+
+            let size = result?;
+
+            // Shell exited (the command called exit or the process was killed)
+            if size == 0 {
+                return Err(SshSessionError::PersistentShellClosed);
+            }
+
+            if is_stdout {
+                stdout.extend_from_slice(&stdout_buf[..size]);
+                exit_code = find_exit_code(&mut stdout, stdout_marker.as_bytes());
+            } else {
+                stderr.extend_from_slice(&stderr_buf[..size]);
+                if let Some(pos) = find(&stderr, stderr_marker.as_bytes()) {
+                    stderr.truncate(pos);
+                    stderr_end = Some(pos);
+                }
+            }
+        }
+
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&stdout).to_string(),
+            stderr: String::from_utf8_lossy(&stderr).to_string(),
+            exit_code: exit_code.unwrap(),
+            stdout_truncated: false,
+            stderr_truncated: false,
+        })
+    }
+
+    pub async fn close(mut self) {
+        let _ = self.channel.send_eof().await;
+        let _ = self.channel.close().await;
+    }
+}
+
+// Looks for "\n<marker>:<exit_code>\n" and cuts it off the output
+fn find_exit_code(stdout: &mut Vec<u8>, marker: &[u8]) -> Option<i32> {
+    let pos = find(stdout, marker)?;
+    let code_start = pos + marker.len();
+    let code_len = stdout[code_start..].iter().position(|b| *b == b'\n')?;
+
+    let exit_code = std::str::from_utf8(&stdout[code_start..code_start + code_len])
+        .ok()?
+        .parse()
+        .ok()?;
+
+    stdout.truncate(pos);
+    Some(exit_code)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_exit_code;
+
+    #[test]
+    fn test_find_exit_code() {
+        let marker = b"\n__M__:";
+
+        let mut stdout = b"hello\n\n__M__:1".to_vec();
+        assert_eq!(find_exit_code(&mut stdout, marker), None);
+
+        stdout.extend_from_slice(b"27\n");
+        assert_eq!(find_exit_code(&mut stdout, marker), Some(127));
+        assert_eq!(stdout, b"hello\n");
+    }
+}
@@ -2,11 +2,13 @@ use std::{sync::Arc, time::Duration};
 
 use futures::Future;
 use rust_extensions::{date_time::DateTimeAsMicroseconds, StrOrString, UnsafeValue};
+use serde::de::DeserializeOwned;
 use tokio::sync::Mutex;
 
 use crate::{
-    RemotePortForwardError, SshAsyncChannel, SshCredentials, SshPortForwardTunnel,
-    SshSessionSingleThreaded, SshSessionWrapper,
+    CommandOutput, RemotePortForwardError, ShellCommand, SshAsyncChannel, SshCommand,
+    SshCredentials, SshExpectSession, SshPortForwardTunnel, SshSessionSingleThreaded,
+    SshSessionWrapper, SshSftp,
 };
 
 use super::SshSessionError;
@@ -133,6 +135,41 @@ impl SshSession {
             .await
     }
 
+    // SFTP channel is opened once per connection and shared by all the SshSftp instances of the session
+    pub async fn sftp(&self, operation_timeout: Duration) -> Result<SshSftp, SshSessionError> {
+        let mut write_access = self.inner.inner.lock().await;
+        let ssh_session = write_access.get(&self.inner.credentials).await?;
+
+        if write_access.sftp.is_none() {
+            let future = ssh_session.open_sftp();
+            let sftp = self
+                .inner
+                .execute_with_timeout(&mut write_access, future, operation_timeout)
+                .await?;
+            write_access.sftp = Some(Arc::new(sftp));
+        }
+
+        Ok(SshSftp::new(
+            write_access.sftp.as_ref().unwrap().clone(),
+            self.inner.clone(),
+            operation_timeout,
+        ))
+    }
+
+    // Opens a named subsystem (netconf, sftp or custom one). Channel is a bidirectional byte stream
+    pub async fn open_subsystem(
+        &self,
+        name: &str,
+        open_timeout: Duration,
+    ) -> Result<SshAsyncChannel, SshSessionError> {
+        let mut write_access = self.inner.inner.lock().await;
+        let ssh_session = write_access.get(&self.inner.credentials).await?;
+        let future = ssh_session.open_subsystem(name);
+        self.inner
+            .execute_with_timeout(&mut write_access, future, open_timeout)
+            .await
+    }
+
     async fn get_home_variable(
         &self,
         ssh_session: &SshSessionWrapper,
@@ -200,23 +237,177 @@ impl SshSession {
         };
 
         self.inner
-            .execute_with_timeout(&mut write_access, future, Duration::from_secs(10))
+            .execute_with_timeout(&mut write_access, future, execute_timeout)
             .await
     }
 
+    // Accepts &str, String or ShellCommand
     pub async fn execute_command(
         &self,
-        command: &str,
+        command: impl AsRef<str>,
         execute_timeout: Duration,
     ) -> Result<(String, i32), SshSessionError> {
         let mut write_access = self.inner.inner.lock().await;
         let ssh_session = write_access.get(&self.inner.credentials).await?;
-        let future = ssh_session.execute_command(command);
+        let future = ssh_session.execute_command(command.as_ref());
         self.inner
             .execute_with_timeout(&mut write_access, future, execute_timeout)
             .await
     }
 
+    pub async fn execute(
+        &self,
+        command: &SshCommand,
+        execute_timeout: Duration,
+    ) -> Result<CommandOutput, SshSessionError> {
+        let mut write_access = self.inner.inner.lock().await;
+        let ssh_session = write_access.get(&self.inner.credentials).await?;
+        let future = ssh_session.execute_ssh_command(command, execute_timeout);
+        self.inner
+            .execute_with_timeout(&mut write_access, future, execute_timeout)
+            .await
+    }
+
+    pub async fn execute_json<T: DeserializeOwned>(
+        &self,
+        command: &SshCommand,
+        execute_timeout: Duration,
+    ) -> Result<T, SshSessionError> {
+        let output = self.execute(command, execute_timeout).await?;
+        output.parse_json()
+    }
+
+    // For tools which print one JSON document per line
+    pub async fn execute_json_lines<T: DeserializeOwned>(
+        &self,
+        command: &SshCommand,
+        execute_timeout: Duration,
+    ) -> Result<Vec<T>, SshSessionError> {
+        let output = self.execute(command, execute_timeout).await?;
+        output.parse_json_lines()
+    }
+
+    // Runs the command in the long-lived shell channel of the session instead of opening a new channel.
+    // Shell is reopened if it died. State such as `cd` or variables is shared between the commands
+    pub async fn execute_in_shell(
+        &self,
+        command: impl AsRef<str>,
+        execute_timeout: Duration,
+    ) -> Result<CommandOutput, SshSessionError> {
+        let mut write_access = self.inner.inner.lock().await;
+        let ssh_session = write_access.get(&self.inner.credentials).await?;
+
+        let is_alive = match write_access.persistent_shell.as_ref() {
+            Some(shell) => shell.is_alive(),
+            None => false,
+        };
+
+        if !is_alive {
+            let future = ssh_session.open_persistent_shell();
+            let shell = self
+                .inner
+                .execute_with_timeout(&mut write_access, future, execute_timeout)
+                .await?;
+            write_access.persistent_shell = Some(shell);
+        }
+
+        let shell = write_access.persistent_shell.as_mut().unwrap();
+        let result = tokio::time::timeout(execute_timeout, shell.execute(command.as_ref())).await;
+
+        match result {
+            Ok(Ok(output)) => Ok(output),
+            Ok(Err(err)) => {
+                // State of the shell is unknown. Next command gets a fresh one
+                if let Some(shell) = write_access.persistent_shell.take() {
+                    shell.close().await;
+                }
+                Err(err)
+            }
+            Err(_) => {
+                if let Some(shell) = write_access.persistent_shell.take() {
+                    shell.close().await;
+                }
+                Err(SshSessionError::Timeout)
+            }
+        }
+    }
+
+    // Uploads the script into a private temp file, runs it with the interpreter and removes the file.
+    // interpreter is used as it is (bash, /usr/bin/env python3), args are quoted
+    pub async fn run_script(
+        &self,
+        content: &str,
+        interpreter: &str,
+        args: &[&str],
+        execute_timeout: Duration,
+    ) -> Result<CommandOutput, SshSessionError> {
+        // mktemp creates the file with 0600 permissions
+        let (temp_file, exit_code) = self
+            .execute_command("mktemp /tmp/my-ssh-script.XXXXXXXXXX", execute_timeout)
+            .await?;
+
+        if exit_code != 0 {
+            return Err(SshSessionError::Other(format!(
+                "Can not create temp file for the script. Exit code: {}. Output: {}",
+                exit_code, temp_file
+            )));
+        }
+
+        let temp_file = temp_file.trim();
+
+        let result = self
+            .upload_and_run_script(temp_file, content, interpreter, args, execute_timeout)
+            .await;
+
+        // If the script timed out the session is reconnected here
+        let remove_command = ShellCommand::new("rm").arg("-f").arg(temp_file);
+        if let Err(err) = self.execute_command(&remove_command, execute_timeout).await {
+            println!(
+                "Can not remove script temp file {} at [{}]. Err: {:?}",
+                temp_file,
+                self.inner.credentials.to_string(),
+                err
+            );
+        }
+
+        result
+    }
+
+    async fn upload_and_run_script(
+        &self,
+        temp_file: &str,
+        content: &str,
+        interpreter: &str,
+        args: &[&str],
+        execute_timeout: Duration,
+    ) -> Result<CommandOutput, SshSessionError> {
+        self.upload_file(temp_file, content.as_bytes(), 0o600, execute_timeout)
+            .await?;
+
+        let command = SshCommand::from_command_line(interpreter)
+            .arg(temp_file)
+            .args(args);
+
+        self.execute(&command, execute_timeout).await
+    }
+
+    // Opens a PTY channel running the command (or the login shell if None) for expect-style scripting
+    pub async fn start_expect_session(
+        &self,
+        command: Option<&str>,
+        open_timeout: Duration,
+    ) -> Result<SshExpectSession, SshSessionError> {
+        let mut write_access = self.inner.inner.lock().await;
+        let ssh_session = write_access.get(&self.inner.credentials).await?;
+        let future = ssh_session.open_pty_channel(command, "xterm");
+        let channel = self
+            .inner
+            .execute_with_timeout(&mut write_access, future, open_timeout)
+            .await?;
+
+        Ok(SshExpectSession::new(channel))
+    }
+
     pub async fn disconnect(&self, reason: &str) {
         self.inner.disconnect(reason).await;
     }
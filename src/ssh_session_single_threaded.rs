@@ -2,11 +2,16 @@ use std::{net::SocketAddr, sync::Arc};
 
 use async_ssh2_lite::{AsyncSession, SessionConfiguration};
 
-use crate::{SshAsyncSession, SshCredentials, SshSessionError, SshSessionWrapper};
+use crate::{
+    SshAsyncSession, SshAsyncSftp, SshCredentials, SshPersistentShell, SshSessionError,
+    SshSessionWrapper,
+};
 
 pub struct SshSessionSingleThreaded {
     pub ssh_session: Option<Arc<SshSessionWrapper>>,
     pub home_variable: Option<String>,
+    pub persistent_shell: Option<SshPersistentShell>,
+    pub sftp: Option<Arc<SshAsyncSftp>>,
 }
 
 impl SshSessionSingleThreaded {
@@ -14,6 +19,8 @@ impl SshSessionSingleThreaded {
         Self {
             ssh_session: None,
             home_variable: None,
+            persistent_shell: None,
+            sftp: None,
         }
     }
 
@@ -30,6 +37,8 @@ impl SshSessionSingleThreaded {
     }
 
     pub async fn disconnect(&mut self, description: &str) {
+        self.persistent_shell = None;
+        self.sftp = None;
         if let Some(session) = self.ssh_session.take() {
             session.disconnect(description).await;
         }
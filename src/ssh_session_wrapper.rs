@@ -1,11 +1,14 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 use async_ssh2_lite::util::ConnectInfo;
 use futures::AsyncReadExt;
 use rust_extensions::StrOrString;
 use tokio::io::AsyncWriteExt;
 
-use crate::{SshAsyncChannel, SshAsyncSession, SshSessionError};
+use crate::{
+    CommandOutput, SshAsyncChannel, SshAsyncSession, SshAsyncSftp, SshCommand, SshPersistentShell,
+    SshSessionError,
+};
 
 pub struct SshSessionWrapper {
     ssh_session: SshAsyncSession,
@@ -43,6 +46,17 @@ impl SshSessionWrapper {
         Ok(result)
     }
 
+    pub async fn open_sftp(&self) -> Result<SshAsyncSftp, SshSessionError> {
+        let result = self.ssh_session.sftp().await?;
+        Ok(result)
+    }
+
+    pub async fn open_subsystem(&self, name: &str) -> Result<SshAsyncChannel, SshSessionError> {
+        let mut channel = self.ssh_session.channel_session().await?;
+        channel.subsystem(name).await?;
+        Ok(channel)
+    }
+
     pub async fn start_port_forward(
         &self,
         remote_port: u16,
@@ -84,6 +98,101 @@ impl SshSessionWrapper {
         Ok((result, channel.exit_status()?))
     }
 
+    pub async fn execute_ssh_command(
+        &self,
+        command: &SshCommand,
+        execute_timeout: Duration,
+    ) -> Result<CommandOutput, SshSessionError> {
+        command.validate()?;
+
+        if let Some(run_as) = command.get_run_as() {
+            // sudo and su reset the environment, so variables always go inline
+            let command_line = command.build_command_line(command.get_env());
+
+            if let Some(password) = run_as.password.as_ref() {
+                let channel = self
+                    .open_pty_channel(Some(command_line.as_str()), "xterm")
+                    .await?;
+                return crate::execute_with_password_prompt(
+                    channel,
+                    run_as.method,
+                    password,
+                    execute_timeout,
+                )
+                .await;
+            }
+
+            let mut channel = self.ssh_session.channel_session().await?;
+            channel.exec(command_line.as_str()).await?;
+            let output =
+                crate::read_command_output(&mut channel, command.get_output_limits()).await?;
+            return crate::check_run_as_output(output);
+        }
+
+        let mut channel = self.ssh_session.channel_session().await?;
+
+        // Servers accept only variables listed in AcceptEnv. The rest goes inline
+        let mut inline_env = Vec::new();
+        for (name, value) in command.get_env() {
+            if channel.setenv(name, value).await.is_err() {
+                inline_env.push((name.to_string(), value.to_string()));
+            }
+        }
+
+        channel
+            .exec(command.build_command_line(&inline_env).as_str())
+            .await?;
+
+        crate::read_command_output(&mut channel, command.get_output_limits()).await
+    }
+
+    pub async fn open_persistent_shell(&self) -> Result<SshPersistentShell, SshSessionError> {
+        let mut channel = self.ssh_session.channel_session().await?;
+        channel.exec("sh").await?;
+        Ok(SshPersistentShell::new(channel))
+    }
+
+    pub async fn open_pty_channel(
+        &self,
+        command: Option<&str>,
+        term: &str,
+    ) -> Result<SshAsyncChannel, SshSessionError> {
+        let mut channel = self.ssh_session.channel_session().await?;
+
+        channel.request_pty(term, None, None).await?;
+
+        match command {
+            Some(command) => channel.exec(command).await?,
+            None => channel.shell().await?,
+        }
+
+        Ok(channel)
+    }
+
+    // Channel is returned right after exec, so stdin and stdout can be streamed
+    pub async fn open_exec_channel(
+        &self,
+        command: &str,
+    ) -> Result<SshAsyncChannel, SshSessionError> {
+        let mut channel = self.ssh_session.channel_session().await?;
+        channel.exec(command).await?;
+        Ok(channel)
+    }
+
+    pub async fn open_scp_send(
+        &self,
+        remote_path: &Path,
+        mode: i32,
+        size: u64,
+        times: Option<(u64, u64)>,
+    ) -> Result<SshAsyncChannel, SshSessionError> {
+        let result = self
+            .ssh_session
+            .scp_send(remote_path, mode, size, times)
+            .await?;
+        Ok(result)
+    }
+
     pub async fn upload_file(
         &self,
         remote_path: String,
@@ -0,0 +1,437 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use futures::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::{
+    read_command_output, shell_quote_path, CommandOutputLimits, SshAsyncChannel, SshSession,
+    SshSessionError,
+};
+
+use super::{SshArchiveCompression, SshArchiveOptions, SshTransferOptions, SshTransferProgress};
+
+// Chunks in flight between the packing thread and the channel. Together with chunk_size it bounds memory
+const CHUNKS_IN_FLIGHT: usize = 4;
+
+impl SshSession {
+    // Packs the local directory with tar on the fly and unpacks it remotely with `tar xf -` in one exec channel.
+    // No temp archive is created on either side. Returns the amount of bytes sent
+    pub async fn upload_tree_as_archive(
+        &self,
+        local_dir: impl AsRef<Path>,
+        remote_dir: &str,
+        options: &SshArchiveOptions,
+    ) -> Result<u64, SshSessionError> {
+        let transfer = &options.transfer;
+        let remote_dir = shell_quote_path(remote_dir);
+
+        let command = match options.compression {
+            SshArchiveCompression::None => {
+                format!("mkdir -p {} && cd {} && tar xf -", remote_dir, remote_dir)
+            }
+            SshArchiveCompression::Gzip => format!(
+                "mkdir -p {} && cd {} && gzip -dc | tar xf -",
+                remote_dir, remote_dir
+            ),
+            SshArchiveCompression::Zstd => format!(
+                "mkdir -p {} && cd {} && zstd -dc | tar xf -",
+                remote_dir, remote_dir
+            ),
+        };
+
+        let mut channel = self
+            .open_archive_channel(command.as_str(), transfer)
+            .await?;
+
+        let (sender, mut receiver) = mpsc::channel::<Vec<u8>>(CHUNKS_IN_FLIGHT);
+        let local_dir = local_dir.as_ref().to_path_buf();
+        let compression = options.compression;
+        let chunk_size = transfer.chunk_size.max(1);
+
+        let packer = tokio::task::spawn_blocking(move || {
+            let writer = std::io::BufWriter::with_capacity(chunk_size, ChunkSender { sender });
+            pack(local_dir.as_path(), compression, writer)
+        });
+
+        let started = Instant::now();
+        let mut sent = 0u64;
+
+        let send_result = async {
+            while let Some(chunk) =
+                super::wait_io(transfer, async { Ok(receiver.recv().await) }).await?
+            {
+                super::wait_io(transfer, channel.write_all(&chunk)).await?;
+                sent += chunk.len() as u64;
+                report_progress(transfer, sent, started);
+            }
+
+            super::wait_io(transfer, channel.flush()).await?;
+            channel.send_eof().await?;
+            Ok::<_, SshSessionError>(())
+        }
+        .await;
+
+        // Stops the packing thread if sending failed
+        drop(receiver);
+        let pack_result = join_blocking(packer).await;
+
+        if let Err(err) = send_result {
+            let _ = channel.close().await;
+            return Err(err);
+        }
+
+        pack_result?;
+
+        finish_archive_channel(&mut channel, transfer).await?;
+
+        Ok(sent)
+    }
+
+    // Packs the remote directory with `tar cf -` and unpacks the stream locally on the fly.
+    // Returns the amount of bytes received
+    pub async fn download_tree_as_archive(
+        &self,
+        remote_dir: &str,
+        local_dir: impl AsRef<Path>,
+        options: &SshArchiveOptions,
+    ) -> Result<u64, SshSessionError> {
+        let transfer = &options.transfer;
+        let remote_dir = shell_quote_path(remote_dir);
+
+        let command = match options.compression {
+            SshArchiveCompression::None => format!("cd {} && tar cf - .", remote_dir),
+            SshArchiveCompression::Gzip => format!("cd {} && tar cf - . | gzip -c", remote_dir),
+            SshArchiveCompression::Zstd => format!("cd {} && tar cf - . | zstd -c", remote_dir),
+        };
+
+        tokio::fs::create_dir_all(local_dir.as_ref()).await?;
+
+        let mut channel = self
+            .open_archive_channel(command.as_str(), transfer)
+            .await?;
+
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>(CHUNKS_IN_FLIGHT);
+        let local_dir: PathBuf = local_dir.as_ref().to_path_buf();
+        let compression = options.compression;
+
+        let unpacker = tokio::task::spawn_blocking(move || {
+            let reader = ChunkReceiver {
+                receiver,
+                chunk: Vec::new(),
+                position: 0,
+            };
+            unpack(reader, compression, local_dir.as_path())
+        });
+
+        let started = Instant::now();
+        let mut received = 0u64;
+
+        let receive_result = async {
+            let mut stdout = channel.stream(0);
+            let mut buf = vec![0u8; transfer.chunk_size.max(1)];
+
+            loop {
+                let size = super::wait_io(transfer, stdout.read(&mut buf)).await?;
+
+                if size == 0 {
+                    break;
+                }
+
+                // Unpacking thread stopped; its error is reported below
+                if sender.send(buf[..size].to_vec()).await.is_err() {
+                    break;
+                }
+
+                received += size as u64;
+                report_progress(transfer, received, started);
+            }
+
+            Ok::<_, SshSessionError>(())
+        }
+        .await;
+
+        drop(sender);
+        let unpack_result = join_blocking(unpacker).await;
+
+        if let Err(err) = receive_result {
+            let _ = channel.close().await;
+            return Err(err);
+        }
+
+        if let Err(err) = unpack_result {
+            // Remote tar failed before sending anything; its stderr explains more
+            if received == 0 {
+                finish_archive_channel(&mut channel, transfer).await?;
+            }
+
+            let _ = channel.close().await;
+            return Err(err);
+        }
+
+        finish_archive_channel(&mut channel, transfer).await?;
+
+        Ok(received)
+    }
+
+    async fn open_archive_channel(
+        &self,
+        command: &str,
+        options: &SshTransferOptions,
+    ) -> Result<SshAsyncChannel, SshSessionError> {
+        let mut write_access = self.inner.inner.lock().await;
+        let ssh_session = write_access.get(&self.inner.credentials).await?;
+        let future = ssh_session.open_exec_channel(command);
+        self.inner
+            .execute_with_timeout(&mut write_access, future, options.inactivity_timeout)
+            .await
+    }
+}
+
+// Collects stderr and the exit code of remote tar
+async fn finish_archive_channel(
+    channel: &mut SshAsyncChannel,
+    options: &SshTransferOptions,
+) -> Result<(), SshSessionError> {
+    let output = match tokio::time::timeout(
+        options.inactivity_timeout,
+        read_command_output(channel, &CommandOutputLimits::default()),
+    )
+    .await
+    {
+        Ok(result) => result?,
+        Err(_) => return Err(SshSessionError::Timeout),
+    };
+
+    if !output.is_success() {
+        return Err(SshSessionError::Other(format!(
+            "Remote tar failed with exit code {}. {}",
+            output.exit_code, output.stderr
+        )));
+    }
+
+    Ok(())
+}
+
+fn report_progress(options: &SshTransferOptions, bytes_done: u64, started: Instant) {
+    let elapsed = started.elapsed();
+
+    options.report_progress(SshTransferProgress {
+        bytes_done,
+        total: None,
+        bytes_per_second: bytes_done as f64 / elapsed.as_secs_f64().max(0.001),
+        elapsed,
+    });
+}
+
+async fn join_blocking(
+    handle: tokio::task::JoinHandle<std::io::Result<()>>,
+) -> Result<(), SshSessionError> {
+    match handle.await {
+        Ok(result) => Ok(result?),
+        Err(err) => Err(SshSessionError::Other(format!(
+            "Archive thread failed: {}",
+            err
+        ))),
+    }
+}
+
+// Symlinks are stored as symlinks
+fn pack(
+    local_dir: &Path,
+    compression: SshArchiveCompression,
+    writer: impl Write,
+) -> std::io::Result<()> {
+    match compression {
+        SshArchiveCompression::None => {
+            let mut builder = tar::Builder::new(writer);
+            builder.follow_symlinks(false);
+            builder.append_dir_all(".", local_dir)?;
+            builder.into_inner()?.flush()
+        }
+        SshArchiveCompression::Gzip => {
+            let encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+            let mut builder = tar::Builder::new(encoder);
+            builder.follow_symlinks(false);
+            builder.append_dir_all(".", local_dir)?;
+            builder.into_inner()?.finish()?.flush()
+        }
+        SshArchiveCompression::Zstd => {
+            let encoder = zstd::Encoder::new(writer, 0)?;
+            let mut builder = tar::Builder::new(encoder);
+            builder.follow_symlinks(false);
+            builder.append_dir_all(".", local_dir)?;
+            builder.into_inner()?.finish()?.flush()
+        }
+    }
+}
+
+fn unpack(
+    reader: impl Read,
+    compression: SshArchiveCompression,
+    local_dir: &Path,
+) -> std::io::Result<()> {
+    match compression {
+        SshArchiveCompression::None => unpack_archive(tar::Archive::new(reader), local_dir),
+        SshArchiveCompression::Gzip => unpack_archive(
+            tar::Archive::new(flate2::read::GzDecoder::new(reader)),
+            local_dir,
+        ),
+        SshArchiveCompression::Zstd => {
+            unpack_archive(tar::Archive::new(zstd::Decoder::new(reader)?), local_dir)
+        }
+    }
+}
+
+fn unpack_archive(mut archive: tar::Archive<impl Read>, local_dir: &Path) -> std::io::Result<()> {
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    archive.unpack(local_dir)
+}
+
+// Write side of the bridge from the blocking tar thread to the async channel
+struct ChunkSender {
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sender.blocking_send(buf.to_vec()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Archive upload is stopped")
+        })?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Read side of the bridge from the async channel to the blocking tar thread
+struct ChunkReceiver {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Read for ChunkReceiver {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position >= self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+
+        let size = buf.len().min(self.chunk.len() - self.position);
+        buf[..size].copy_from_slice(&self.chunk[self.position..self.position + size]);
+        self.position += size;
+
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+    };
+
+    use tokio::sync::mpsc;
+
+    use super::{pack, unpack, ChunkReceiver, ChunkSender, SshArchiveCompression};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "my-ssh-archive-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn create_tree(root: &Path) {
+        std::fs::create_dir_all(root.join("nested/deeper")).unwrap();
+        std::fs::write(root.join("nested/deeper/file.txt"), b"nested content").unwrap();
+
+        std::fs::write(root.join("run.sh"), b"#!/bin/sh\necho ok\n").unwrap();
+        std::fs::set_permissions(root.join("run.sh"), std::fs::Permissions::from_mode(0o750))
+            .unwrap();
+
+        std::os::unix::fs::symlink("run.sh", root.join("link")).unwrap();
+    }
+
+    fn check_tree(root: &Path) {
+        assert_eq!(
+            std::fs::read(root.join("nested/deeper/file.txt")).unwrap(),
+            b"nested content"
+        );
+
+        let mode = std::fs::metadata(root.join("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o750);
+
+        assert_eq!(
+            std::fs::read_link(root.join("link")).unwrap(),
+            Path::new("run.sh")
+        );
+    }
+
+    // Goes through the same bridge as the transfer: packing thread -> ChunkSender -> ChunkReceiver -> unpack
+    fn round_trip(compression: SshArchiveCompression, name: &str) {
+        let source = temp_dir(format!("{}-src", name).as_str());
+        let destination = temp_dir(format!("{}-dst", name).as_str());
+        create_tree(&source);
+
+        let (sender, receiver) = mpsc::channel::<Vec<u8>>(2);
+
+        let packer = {
+            let source = source.clone();
+            std::thread::spawn(move || {
+                let writer = std::io::BufWriter::with_capacity(1024, ChunkSender { sender });
+                pack(source.as_path(), compression, writer)
+            })
+        };
+
+        let reader = ChunkReceiver {
+            receiver,
+            chunk: Vec::new(),
+            position: 0,
+        };
+
+        unpack(reader, compression, destination.as_path()).unwrap();
+        packer.join().unwrap().unwrap();
+
+        check_tree(&destination);
+
+        std::fs::remove_dir_all(&source).unwrap();
+        std::fs::remove_dir_all(&destination).unwrap();
+    }
+
+    #[test]
+    fn test_round_trip_without_compression() {
+        round_trip(SshArchiveCompression::None, "none");
+    }
+
+    #[test]
+    fn test_round_trip_gzip() {
+        round_trip(SshArchiveCompression::Gzip, "gzip");
+    }
+
+    #[test]
+    fn test_round_trip_zstd() {
+        round_trip(SshArchiveCompression::Zstd, "zstd");
+    }
+}
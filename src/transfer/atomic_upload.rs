@@ -0,0 +1,128 @@
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{shell_quote_path, SshSession, SshSessionError};
+
+use super::{FuturesWriteCompat, HashingReader, SshAtomicUploadOptions};
+
+impl SshSession {
+    // Content goes to a temp file in the same directory, which is renamed over remote_path when it is complete.
+    // Readers see either the previous or the new version, never a truncated one
+    pub async fn upload_stream_atomic(
+        &self,
+        remote_path: &str,
+        reader: &mut (impl AsyncRead + Unpin),
+        size: u64,
+        options: &SshAtomicUploadOptions,
+    ) -> Result<(), SshSessionError> {
+        let temp_path = get_temp_path(remote_path);
+
+        let result = self
+            .write_temp_and_replace(remote_path, temp_path.as_str(), reader, size, options)
+            .await;
+
+        if result.is_err() {
+            if let Ok(sftp) = self.sftp(options.transfer.inactivity_timeout).await {
+                let _ = sftp.remove_file(temp_path.as_str()).await;
+            }
+        }
+
+        result
+    }
+
+    pub async fn upload_file_atomic(
+        &self,
+        remote_path: &str,
+        content: &[u8],
+        options: &SshAtomicUploadOptions,
+    ) -> Result<(), SshSessionError> {
+        let mut reader = content;
+        self.upload_stream_atomic(remote_path, &mut reader, content.len() as u64, options)
+            .await
+    }
+
+    async fn write_temp_and_replace(
+        &self,
+        remote_path: &str,
+        temp_path: &str,
+        reader: &mut (impl AsyncRead + Unpin),
+        size: u64,
+        options: &SshAtomicUploadOptions,
+    ) -> Result<(), SshSessionError> {
+        let transfer = &options.transfer;
+        let sftp = self.sftp(transfer.inactivity_timeout).await?;
+
+        let file = sftp.create(temp_path, options.mode).await?;
+
+        let mut writer = FuturesWriteCompat::new(file);
+        let mut reader = HashingReader::new(reader.take(size), transfer.verify_checksum);
+
+        let copied =
+            super::copy_stream_with_progress(&mut reader, &mut writer, transfer, Some(size), 0)
+                .await?;
+
+        if copied != size {
+            return Err(SshSessionError::Other(format!(
+                "Upload of {} declared {} bytes, but the stream ended after {} bytes",
+                remote_path, size, copied
+            )));
+        }
+
+        let mut file = writer.into_inner();
+
+        // fsync@openssh.com is not supported by every server
+        let _ = tokio::time::timeout(transfer.inactivity_timeout, file.fsync()).await;
+        drop(file);
+
+        // Mode of a created file is affected by umask
+        sftp.chmod(temp_path, options.mode as u32).await?;
+
+        if let Some((uid, gid)) = options.owner {
+            sftp.chown(temp_path, uid, gid).await?;
+        }
+
+        if let Some(local_hash) = reader.finish() {
+            self.verify_remote_sha256(temp_path, None, local_hash, transfer)
+                .await?;
+        }
+
+        // SFTP v3 rename does not replace an existing file, rename(2) behind mv does it atomically
+        let target = shell_quote_path(remote_path);
+        let mut command = String::new();
+
+        if let Some(backup_suffix) = options.backup_suffix.as_ref() {
+            let backup = shell_quote_path(format!("{}{}", remote_path, backup_suffix).as_str());
+            command.push_str(
+                format!(
+                    "{{ [ ! -e {} ] || cp -p {} {}; }} && ",
+                    target, target, backup
+                )
+                .as_str(),
+            );
+        }
+
+        command.push_str(format!("mv -f {} {}", shell_quote_path(temp_path), target).as_str());
+
+        let (output, exit_code) = self
+            .execute_command(command, transfer.inactivity_timeout)
+            .await?;
+
+        if exit_code != 0 {
+            return Err(SshSessionError::Other(format!(
+                "Can not replace {}. Exit code: {}. {}",
+                remote_path, exit_code, output
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn get_temp_path(remote_path: &str) -> String {
+    let id = DateTimeAsMicroseconds::now().unix_microseconds;
+
+    match remote_path.rsplit_once('/') {
+        Some((dir, name)) => format!("{}/.{}.my-ssh-{}.tmp", dir, name, id),
+        None => format!(".{}.my-ssh-{}.tmp", remote_path, id),
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+use crate::{shell_quote_path, SshCommand, SshSession, SshSessionError};
+
+use super::SshTransferOptions;
+
+const HASH_BUFFER_SIZE: usize = 256 * 1024;
+
+// sha256sum is missing on macOS and some BSDs, shasum is used there
+pub(crate) fn remote_sha256_command(remote_path: &str, prefix_len: Option<u64>) -> String {
+    let path = shell_quote_path(remote_path);
+
+    let read = match prefix_len {
+        Some(len) => format!("head -c {} {}", len, path),
+        None => format!("cat {}", path),
+    };
+
+    format!(
+        "if command -v sha256sum >/dev/null 2>&1; then h='sha256sum'; else h='shasum -a 256'; fi; [ -f {} ] && {} | $h",
+        path, read
+    )
+}
+
+fn parse_sha256_output(output: &str) -> Option<String> {
+    let hash = output.split_whitespace().next()?;
+
+    if hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(hash.to_lowercase())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() * 2);
+
+    for byte in bytes {
+        result.push_str(format!("{:02x}", byte).as_str());
+    }
+
+    result
+}
+
+// Hashes first len bytes of the local file
+pub(crate) async fn local_sha256(path: &Path, len: u64) -> Result<String, SshSessionError> {
+    let mut file = tokio::fs::File::open(path).await?.take(len);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_BUFFER_SIZE];
+
+    loop {
+        let size = file.read(&mut buf).await?;
+
+        if size == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..size]);
+    }
+
+    Ok(to_hex(hasher.finalize().as_slice()))
+}
+
+// Hashes everything read through it. Hashing is skipped when it is disabled
+pub(crate) struct HashingReader<R> {
+    reader: R,
+    hasher: Option<Sha256>,
+}
+
+impl<R> HashingReader<R> {
+    pub fn new(reader: R, enabled: bool) -> Self {
+        Self {
+            reader,
+            hasher: enabled.then(Sha256::new),
+        }
+    }
+
+    pub fn finish(self) -> Option<String> {
+        self.hasher
+            .map(|hasher| to_hex(hasher.finalize().as_slice()))
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let filled_before = buf.filled().len();
+
+        let result = Pin::new(&mut self.reader).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = &result {
+            if let Some(hasher) = self.hasher.as_mut() {
+                hasher.update(&buf.filled()[filled_before..]);
+            }
+        }
+
+        result
+    }
+}
+
+impl SshSession {
+    // Hashes the remote file (or its first prefix_len bytes) on the remote side
+    pub(crate) async fn remote_sha256(
+        &self,
+        remote_path: &str,
+        prefix_len: Option<u64>,
+        execute_timeout: Duration,
+    ) -> Result<String, SshSessionError> {
+        let command = SshCommand::from_command_line(remote_sha256_command(remote_path, prefix_len));
+        let output = self.execute(&command, execute_timeout).await?;
+
+        if !output.is_success() {
+            return Err(SshSessionError::Other(format!(
+                "Can not calculate sha256 of {}. Exit code: {}. {}",
+                remote_path, output.exit_code, output.stderr
+            )));
+        }
+
+        parse_sha256_output(output.stdout.as_str()).ok_or_else(|| {
+            SshSessionError::Other(format!(
+                "Can not parse sha256 of {}: {}",
+                remote_path, output.stdout
+            ))
+        })
+    }
+
+    // libssh2 does not support the check-file SFTP extension, so the remote hash is always calculated with a command
+    pub(crate) async fn verify_remote_sha256(
+        &self,
+        remote_path: &str,
+        prefix_len: Option<u64>,
+        local_hash: String,
+        options: &SshTransferOptions,
+    ) -> Result<(), SshSessionError> {
+        let remote_hash = self
+            .remote_sha256(remote_path, prefix_len, options.checksum_timeout)
+            .await?;
+
+        if remote_hash != local_hash {
+            return Err(SshSessionError::ChecksumMismatch {
+                path: remote_path.to_string(),
+                local: local_hash,
+                remote: remote_hash,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_sha256_output, remote_sha256_command, to_hex};
+
+    #[test]
+    fn test_remote_sha256_command() {
+        assert_eq!(
+            remote_sha256_command("~/dump 1.sql", Some(1024)),
+            "if command -v sha256sum >/dev/null 2>&1; then h='sha256sum'; else h='shasum -a 256'; fi; [ -f ~/'dump 1.sql' ] && head -c 1024 ~/'dump 1.sql' | $h"
+        );
+    }
+
+    #[test]
+    fn test_parse_sha256_output() {
+        let hash = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855";
+
+        assert_eq!(
+            parse_sha256_output(format!("{}  -\n", hash).as_str()),
+            Some(hash.to_lowercase())
+        );
+        assert_eq!(parse_sha256_output("sha256sum: not found\n"), None);
+    }
+
+    #[test]
+    fn test_to_hex() {
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{SshSession, SshSessionError};
+
+use super::{SshRemoteFileInfo, SshTransferOptions};
+
+// Copies the file between two hosts which can not reach each other. Data goes through this process
+// chunk by chunk (SFTP read from source, SCP write to destination), so memory is bounded by chunk_size.
+// With verify_checksum both the source and the destination are compared with the streamed data
+pub async fn copy_between(
+    source: &SshSession,
+    source_path: &str,
+    destination: &SshSession,
+    destination_path: &str,
+    options: &SshTransferOptions,
+) -> Result<SshRemoteFileInfo, SshSessionError> {
+    let mut stream = source.download_stream(source_path, options).await?;
+    let info = stream.info;
+
+    let times = if options.preserve_times {
+        Some((info.mtime.unwrap_or(0), info.atime.unwrap_or(0)))
+    } else {
+        None
+    };
+
+    let (exit_code, digest) = destination
+        .upload_stream_with_times(
+            destination_path,
+            &mut stream,
+            info.size,
+            info.mode as i32,
+            times,
+            options,
+        )
+        .await?;
+
+    if exit_code != 0 {
+        return Err(SshSessionError::Other(format!(
+            "Upload of {} finished with exit code {}",
+            destination_path, exit_code
+        )));
+    }
+
+    // Destination is verified by the upload; source is checked against the digest the upload computed
+    if let Some(local_hash) = digest {
+        source
+            .verify_remote_sha256(source_path, Some(info.size), local_hash, options)
+            .await?;
+    }
+
+    Ok(info)
+}
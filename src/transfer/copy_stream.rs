@@ -0,0 +1,162 @@
+use std::time::Instant;
+
+use futures::{future::Either, Future};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::SshSessionError;
+
+use super::{SshTransferOptions, SshTransferProgress};
+
+// Copies the stream chunk by chunk. Every read and write is limited with inactivity_timeout
+pub async fn copy_stream(
+    reader: &mut (impl AsyncRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+    options: &SshTransferOptions,
+) -> Result<u64, SshSessionError> {
+    copy_stream_with_progress(reader, writer, options, None, 0).await
+}
+
+// total and already_done are reported to the progress subscriber only.
+// Returns the amount of bytes copied by this call
+pub(crate) async fn copy_stream_with_progress(
+    reader: &mut (impl AsyncRead + Unpin),
+    writer: &mut (impl AsyncWrite + Unpin),
+    options: &SshTransferOptions,
+    total: Option<u64>,
+    already_done: u64,
+) -> Result<u64, SshSessionError> {
+    let mut buf = vec![0u8; options.chunk_size.max(1)];
+    let mut copied = 0u64;
+    let started = Instant::now();
+
+    options.report_progress(SshTransferProgress {
+        bytes_done: already_done,
+        total,
+        bytes_per_second: 0.0,
+        elapsed: Default::default(),
+    });
+
+    loop {
+        let size = wait_io(options, reader.read(&mut buf)).await?;
+
+        if size == 0 {
+            break;
+        }
+
+        wait_io(options, writer.write_all(&buf[..size])).await?;
+
+        copied += size as u64;
+
+        let elapsed = started.elapsed();
+        options.report_progress(SshTransferProgress {
+            bytes_done: already_done + copied,
+            total,
+            bytes_per_second: copied as f64 / elapsed.as_secs_f64().max(0.001),
+            elapsed,
+        });
+    }
+
+    wait_io(options, writer.flush()).await?;
+
+    Ok(copied)
+}
+
+// Applies inactivity timeout and cancellation to one IO operation
+pub(crate) async fn wait_io<TResult>(
+    options: &SshTransferOptions,
+    future: impl Future<Output = std::io::Result<TResult>>,
+) -> Result<TResult, SshSessionError> {
+    let future = tokio::time::timeout(options.inactivity_timeout, future);
+
+    let result = match options.cancellation.as_ref() {
+        Some(cancellation) => {
+            let cancelled = cancellation.cancelled();
+            futures::pin_mut!(future, cancelled);
+
+            match futures::future::select(future, cancelled).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => return Err(SshSessionError::Cancelled),
+            }
+        }
+        None => future.await,
+    };
+
+    match result {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(SshSessionError::Timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::AsyncWriteExt;
+
+    use super::copy_stream_with_progress;
+    use crate::{SshCancellationToken, SshSessionError, SshTransferOptions};
+
+    #[tokio::test]
+    async fn test_progress_is_reported() {
+        let mut options = SshTransferOptions {
+            chunk_size: 4,
+            ..Default::default()
+        };
+        let progress = options.subscribe_progress();
+
+        let mut reader = &b"0123456789"[..];
+        let mut writer = Vec::new();
+
+        // Resumed transfer: 5 bytes were there already
+        let copied = copy_stream_with_progress(&mut reader, &mut writer, &options, Some(15), 5)
+            .await
+            .unwrap();
+
+        assert_eq!(copied, 10);
+        assert_eq!(writer, b"0123456789");
+
+        let progress = *progress.borrow();
+        assert_eq!(progress.bytes_done, 15);
+        assert_eq!(progress.total, Some(15));
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_stops_copy() {
+        let token = SshCancellationToken::new();
+        let options = SshTransferOptions {
+            cancellation: Some(token.clone()),
+            ..Default::default()
+        };
+
+        // Remote side sends a chunk and stalls without closing the stream
+        let (mut remote, mut reader) = tokio::io::duplex(64);
+        remote.write_all(b"abc").await.unwrap();
+
+        let mut writer = Vec::new();
+        let copy = copy_stream_with_progress(&mut reader, &mut writer, &options, None, 0);
+        let cancel = async {
+            tokio::task::yield_now().await;
+            token.cancel();
+        };
+
+        let (result, _) = tokio::join!(copy, cancel);
+
+        assert!(matches!(result, Err(SshSessionError::Cancelled)));
+        assert_eq!(writer, b"abc");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_inactivity_timeout() {
+        let options = SshTransferOptions {
+            inactivity_timeout: Duration::from_secs(1),
+            ..Default::default()
+        };
+
+        let (_remote, mut reader) = tokio::io::duplex(64);
+        let mut writer = Vec::new();
+
+        let result = copy_stream_with_progress(&mut reader, &mut writer, &options, None, 0).await;
+
+        assert!(matches!(result, Err(SshSessionError::Timeout)));
+    }
+}
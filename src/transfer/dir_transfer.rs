@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use crate::{SshSession, SshSessionError};
+
+use super::{
+    join_relative_path, PathFilter, SshDirTransferOptions, SshDirTransferReport, SshSymlinkPolicy,
+    SshTransferOptions,
+};
+
+const DEFAULT_DIR_MODE: i32 = 0o755;
+
+impl SshSession {
+    // Walks the local tree and uploads it into remote_path with SFTP directories and SCP files.
+    // Missing directories are created, existing files are overwritten
+    pub async fn upload_dir(
+        &self,
+        local_path: impl AsRef<Path>,
+        remote_path: &str,
+        options: &SshDirTransferOptions,
+    ) -> Result<SshDirTransferReport, SshSessionError> {
+        let filter = PathFilter::new(&options.include, &options.exclude)?;
+        let transfer_options = preserve_permissions(&options.transfer);
+        let sftp = self.sftp(options.transfer.inactivity_timeout).await?;
+
+        let mut report = SshDirTransferReport::default();
+
+        let remote_root = remote_path.trim_end_matches('/').to_string();
+        sftp.mkdir_all(remote_root.as_str(), DEFAULT_DIR_MODE)
+            .await?;
+
+        let mut dirs = vec![(
+            local_path.as_ref().to_path_buf(),
+            remote_root,
+            String::new(),
+        )];
+
+        while let Some((local_dir, remote_dir, relative_dir)) = dirs.pop() {
+            let mut read_dir = match tokio::fs::read_dir(&local_dir).await {
+                Ok(read_dir) => read_dir,
+                Err(err) => {
+                    report.handle_error(options, relative_dir.as_str(), err.into())?;
+                    continue;
+                }
+            };
+
+            loop {
+                let entry = match read_dir.next_entry().await {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => break,
+                    Err(err) => {
+                        report.handle_error(options, relative_dir.as_str(), err.into())?;
+                        break;
+                    }
+                };
+
+                let name = entry.file_name().to_string_lossy().to_string();
+                let relative_path = join_relative_path(relative_dir.as_str(), name.as_str());
+                let local_entry = entry.path();
+                let remote_entry = format!("{}/{}", remote_dir, name);
+
+                if filter.is_excluded(relative_path.as_str()) {
+                    report.skipped.push(relative_path);
+                    continue;
+                }
+
+                let result = async {
+                    let mut metadata = tokio::fs::symlink_metadata(&local_entry).await?;
+
+                    if metadata.file_type().is_symlink() {
+                        match options.symlinks {
+                            SshSymlinkPolicy::Skip => {
+                                report.skipped.push(relative_path.clone());
+                                return Ok(());
+                            }
+                            SshSymlinkPolicy::Preserve => {
+                                if !filter.is_file_included(relative_path.as_str()) {
+                                    report.skipped.push(relative_path.clone());
+                                    return Ok(());
+                                }
+
+                                let target = tokio::fs::read_link(&local_entry).await?;
+                                if sftp.lstat(remote_entry.as_str()).await.is_ok() {
+                                    sftp.remove_file(remote_entry.as_str()).await?;
+                                }
+                                sftp.symlink(remote_entry.as_str(), &target.to_string_lossy())
+                                    .await?;
+                                report.symlinks_created += 1;
+                                return Ok(());
+                            }
+                            SshSymlinkPolicy::Follow => {
+                                metadata = tokio::fs::metadata(&local_entry).await?;
+                            }
+                        }
+                    }
+
+                    if metadata.is_dir() {
+                        if !sftp.exists(remote_entry.as_str()).await? {
+                            sftp.mkdir(remote_entry.as_str(), DEFAULT_DIR_MODE).await?;
+                            report.dirs_created += 1;
+                        }
+
+                        dirs.push((
+                            local_entry.clone(),
+                            remote_entry.clone(),
+                            relative_path.clone(),
+                        ));
+                        return Ok(());
+                    }
+
+                    if !filter.is_file_included(relative_path.as_str()) {
+                        report.skipped.push(relative_path.clone());
+                        return Ok(());
+                    }
+
+                    let exit_code = self
+                        .upload_from_path(&local_entry, remote_entry.as_str(), &transfer_options)
+                        .await?;
+
+                    if exit_code != 0 {
+                        return Err(SshSessionError::Other(format!(
+                            "Upload of {} finished with exit code {}",
+                            remote_entry, exit_code
+                        )));
+                    }
+
+                    report.files_transferred += 1;
+                    report.bytes_transferred += metadata.len();
+
+                    Ok::<_, SshSessionError>(())
+                }
+                .await;
+
+                if let Err(err) = result {
+                    report.handle_error(options, relative_path.as_str(), err)?;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    // Walks the remote tree with SFTP and downloads it into local_path
+    pub async fn download_dir(
+        &self,
+        remote_path: &str,
+        local_path: impl AsRef<Path>,
+        options: &SshDirTransferOptions,
+    ) -> Result<SshDirTransferReport, SshSessionError> {
+        let filter = PathFilter::new(&options.include, &options.exclude)?;
+        let transfer_options = preserve_permissions(&options.transfer);
+        let sftp = self.sftp(options.transfer.inactivity_timeout).await?;
+
+        let mut report = SshDirTransferReport::default();
+
+        tokio::fs::create_dir_all(local_path.as_ref()).await?;
+
+        let mut dirs: Vec<(String, PathBuf, String)> = vec![(
+            remote_path.trim_end_matches('/').to_string(),
+            local_path.as_ref().to_path_buf(),
+            String::new(),
+        )];
+
+        while let Some((remote_dir, local_dir, relative_dir)) = dirs.pop() {
+            let entries = match sftp.list_dir(remote_dir.as_str()).await {
+                Ok(entries) => entries,
+                Err(err) => {
+                    report.handle_error(options, relative_dir.as_str(), err)?;
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                let relative_path = join_relative_path(relative_dir.as_str(), entry.name.as_str());
+                let local_entry = local_dir.join(entry.name.as_str());
+
+                if filter.is_excluded(relative_path.as_str()) {
+                    report.skipped.push(relative_path);
+                    continue;
+                }
+
+                let result = async {
+                    let mut stat = entry.stat.clone();
+
+                    if entry.is_symlink() {
+                        match options.symlinks {
+                            SshSymlinkPolicy::Skip => {
+                                report.skipped.push(relative_path.clone());
+                                return Ok(());
+                            }
+                            SshSymlinkPolicy::Preserve => {
+                                if !filter.is_file_included(relative_path.as_str()) {
+                                    report.skipped.push(relative_path.clone());
+                                    return Ok(());
+                                }
+
+                                let target = sftp.read_link(entry.path.as_str()).await?;
+                                if tokio::fs::symlink_metadata(&local_entry).await.is_ok() {
+                                    tokio::fs::remove_file(&local_entry).await?;
+                                }
+                                tokio::fs::symlink(target, &local_entry).await?;
+                                report.symlinks_created += 1;
+                                return Ok(());
+                            }
+                            SshSymlinkPolicy::Follow => {
+                                stat = sftp.stat(entry.path.as_str()).await?;
+                            }
+                        }
+                    }
+
+                    if stat.is_dir() {
+                        if tokio::fs::metadata(&local_entry).await.is_err() {
+                            tokio::fs::create_dir(&local_entry).await?;
+                            report.dirs_created += 1;
+                        }
+
+                        dirs.push((
+                            entry.path.clone(),
+                            local_entry.clone(),
+                            relative_path.clone(),
+                        ));
+                        return Ok(());
+                    }
+
+                    if !filter.is_file_included(relative_path.as_str()) {
+                        report.skipped.push(relative_path.clone());
+                        return Ok(());
+                    }
+
+                    let info = self
+                        .download_to_path(entry.path.as_str(), &local_entry, &transfer_options)
+                        .await?;
+
+                    report.files_transferred += 1;
+                    report.bytes_transferred += info.size;
+
+                    Ok::<_, SshSessionError>(())
+                }
+                .await;
+
+                if let Err(err) = result {
+                    report.handle_error(options, relative_path.as_str(), err)?;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+fn preserve_permissions(options: &SshTransferOptions) -> SshTransferOptions {
+    SshTransferOptions {
+        preserve_permissions: true,
+        ..options.clone()
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    fs::{FileTimes, Permissions},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use crate::{SshSession, SshSessionError};
+
+use super::{HashingReader, SshFileDownloadStream, SshRemoteFileInfo, SshTransferOptions};
+
+impl SshSession {
+    // Opens the remote file for streaming. Size, mode and times are taken before the file is opened
+    pub async fn download_stream(
+        &self,
+        remote_path: &str,
+        options: &SshTransferOptions,
+    ) -> Result<SshFileDownloadStream, SshSessionError> {
+        let sftp = self.sftp(options.inactivity_timeout).await?;
+        let stat = sftp.stat(remote_path).await?;
+        let file = sftp.open(remote_path).await?;
+
+        Ok(SshFileDownloadStream::new(
+            SshRemoteFileInfo::from(&stat),
+            file,
+        ))
+    }
+
+    // Streams the remote file straight into the local one without buffering it in memory
+    pub async fn download_to_path(
+        &self,
+        remote_path: &str,
+        local_path: impl AsRef<Path>,
+        options: &SshTransferOptions,
+    ) -> Result<SshRemoteFileInfo, SshSessionError> {
+        let mut stream = self.download_stream(remote_path, options).await?;
+        let mut local_file = tokio::fs::File::create(local_path.as_ref()).await?;
+
+        let size = stream.info.size;
+        let mut reader = HashingReader::new(&mut stream, options.verify_checksum);
+
+        let copied =
+            super::copy_stream_with_progress(&mut reader, &mut local_file, options, Some(size), 0)
+                .await?;
+
+        let local_hash = reader.finish();
+
+        // File may grow while it is being downloaded (logs), but must not be shorter
+        if copied < stream.info.size {
+            return Err(SshSessionError::Other(format!(
+                "Remote file {} is {} bytes, but only {} bytes were downloaded",
+                remote_path, stream.info.size, copied
+            )));
+        }
+
+        finish_local_file(local_file, &stream.info, options).await?;
+
+        // Only the downloaded part is compared, since the remote file may have grown
+        if let Some(local_hash) = local_hash {
+            self.verify_remote_sha256(remote_path, Some(copied), local_hash, options)
+                .await?;
+        }
+
+        Ok(stream.info)
+    }
+}
+
+// Flushes the downloaded file to disk and applies mode and times of the remote one if requested
+pub(super) async fn finish_local_file(
+    local_file: tokio::fs::File,
+    info: &SshRemoteFileInfo,
+    options: &SshTransferOptions,
+) -> Result<(), SshSessionError> {
+    local_file.sync_all().await?;
+
+    if options.preserve_permissions {
+        local_file
+            .set_permissions(Permissions::from_mode(info.mode))
+            .await?;
+    }
+
+    if options.preserve_times {
+        let local_file = local_file.into_std().await;
+        let info = *info;
+        tokio::task::spawn_blocking(move || local_file.set_times(to_file_times(&info)))
+            .await
+            .map_err(|err| SshSessionError::Other(err.to_string()))??;
+    }
+
+    Ok(())
+}
+
+fn to_file_times(info: &SshRemoteFileInfo) -> FileTimes {
+    let mut times = FileTimes::new();
+
+    if let Some(mtime) = info.mtime {
+        times = times.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime));
+    }
+
+    if let Some(atime) = info.atime {
+        times = times.set_accessed(SystemTime::UNIX_EPOCH + Duration::from_secs(atime));
+    }
+
+    times
+}
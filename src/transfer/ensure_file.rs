@@ -0,0 +1,173 @@
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+
+use crate::{SshSession, SshSessionError};
+
+use super::{
+    to_hex, SshAtomicUploadOptions, SshEnsureFileOptions, SshEnsureFileResult, SshEnsureFileStatus,
+};
+
+impl SshSession {
+    // Idempotent upload: remote file is compared by size, sha256, mode and owner and touched only on difference.
+    // Content is replaced atomically; only mode/owner differences are fixed in place
+    pub async fn ensure_file(
+        &self,
+        remote_path: &str,
+        content: &[u8],
+        mode: i32,
+        owner: Option<(u32, u32)>,
+        options: &SshEnsureFileOptions,
+    ) -> Result<SshEnsureFileResult, SshSessionError> {
+        let transfer = &options.transfer;
+        let sftp = self.sftp(transfer.inactivity_timeout).await?;
+
+        let stat = match sftp.try_stat(remote_path).await? {
+            Some(stat) => stat,
+            None => {
+                if !options.dry_run {
+                    self.upload_file_atomic(remote_path, content, &to_atomic(options, mode, owner))
+                        .await?;
+                }
+
+                return Ok(SshEnsureFileResult {
+                    status: SshEnsureFileStatus::Created,
+                    content_changed: true,
+                    metadata_changed: true,
+                    diff: build_diff(remote_path, &[], content, options.max_diff_size),
+                });
+            }
+        };
+
+        let content_changed = if stat.size != Some(content.len() as u64) {
+            true
+        } else {
+            let local_hash = to_hex(Sha256::digest(content).as_slice());
+            let remote_hash = self
+                .remote_sha256(remote_path, None, transfer.checksum_timeout)
+                .await?;
+            local_hash != remote_hash
+        };
+
+        let mode_changed = stat.perm.map(|perm| perm & 0o7777) != Some(mode as u32);
+        let owner_changed = match owner {
+            Some((uid, gid)) => stat.uid != Some(uid) || stat.gid != Some(gid),
+            None => false,
+        };
+
+        if !content_changed && !mode_changed && !owner_changed {
+            return Ok(SshEnsureFileResult {
+                status: SshEnsureFileStatus::Unchanged,
+                content_changed: false,
+                metadata_changed: false,
+                diff: None,
+            });
+        }
+
+        let remote_size = stat.size.unwrap_or(u64::MAX);
+
+        // Remote file is downloaded for the diff only if it is small enough
+        let diff = if content_changed
+            && content.len() <= options.max_diff_size
+            && remote_size <= options.max_diff_size as u64
+        {
+            let remote_content = self
+                .download_remote_file(remote_path, transfer.inactivity_timeout)
+                .await?;
+            build_diff(
+                remote_path,
+                remote_content.as_slice(),
+                content,
+                options.max_diff_size,
+            )
+        } else {
+            None
+        };
+
+        if !options.dry_run {
+            if content_changed {
+                self.upload_file_atomic(remote_path, content, &to_atomic(options, mode, owner))
+                    .await?;
+            } else {
+                if mode_changed {
+                    sftp.chmod(remote_path, mode as u32).await?;
+                }
+
+                if let Some((uid, gid)) = owner {
+                    if owner_changed {
+                        sftp.chown(remote_path, uid, gid).await?;
+                    }
+                }
+            }
+        }
+
+        Ok(SshEnsureFileResult {
+            status: SshEnsureFileStatus::Updated,
+            content_changed,
+            metadata_changed: mode_changed || owner_changed,
+            diff,
+        })
+    }
+}
+
+fn to_atomic(
+    options: &SshEnsureFileOptions,
+    mode: i32,
+    owner: Option<(u32, u32)>,
+) -> SshAtomicUploadOptions {
+    SshAtomicUploadOptions {
+        transfer: options.transfer.clone(),
+        mode,
+        owner,
+        backup_suffix: options.backup_suffix.clone(),
+    }
+}
+
+// None for binary content (not UTF-8 or containing zero bytes) and for content above max_size
+fn build_diff(path: &str, old: &[u8], new: &[u8], max_size: usize) -> Option<String> {
+    if old.len() > max_size || new.len() > max_size {
+        return None;
+    }
+
+    let old = as_text(old)?;
+    let new = as_text(new)?;
+
+    let path = path.trim_start_matches('/');
+
+    let diff = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(
+            format!("a/{}", path).as_str(),
+            format!("b/{}", path).as_str(),
+        )
+        .to_string();
+
+    Some(diff)
+}
+
+fn as_text(content: &[u8]) -> Option<&str> {
+    if content.contains(&0) {
+        return None;
+    }
+
+    std::str::from_utf8(content).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_diff;
+
+    #[test]
+    fn test_build_diff() {
+        let diff = build_diff("/etc/app.conf", b"a=1\nb=2\n", b"a=1\nb=3\n", 1024).unwrap();
+
+        assert!(diff.starts_with("--- a/etc/app.conf\n+++ b/etc/app.conf\n"));
+        assert!(diff.contains("-b=2\n"));
+        assert!(diff.contains("+b=3\n"));
+    }
+
+    #[test]
+    fn test_no_diff_for_binary() {
+        assert!(build_diff("/bin/app", b"\x7fELF\0", b"\x7fELF\0\x01", 1024).is_none());
+        assert!(build_diff("/etc/app.conf", b"a=1\n", b"a=2\n", 2).is_none());
+    }
+}
@@ -0,0 +1,41 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+// Lets tokio::io::AsyncWrite based code write into futures::AsyncWrite (SSH channels, SFTP files)
+pub struct FuturesWriteCompat<T> {
+    inner: T,
+}
+
+impl<T> FuturesWriteCompat<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: futures::AsyncWrite + Unpin> tokio::io::AsyncWrite for FuturesWriteCompat<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
@@ -0,0 +1,39 @@
+mod ssh_transfer_options;
+pub use ssh_transfer_options::*;
+mod ssh_transfer_progress;
+pub use ssh_transfer_progress::*;
+mod ssh_remote_file_info;
+pub use ssh_remote_file_info::*;
+mod ssh_file_download_stream;
+pub use ssh_file_download_stream::*;
+mod copy_stream;
+pub use copy_stream::*;
+mod futures_write_compat;
+pub use futures_write_compat::*;
+mod copy_between;
+pub use copy_between::*;
+mod ssh_dir_transfer_options;
+pub use ssh_dir_transfer_options::*;
+mod ssh_resumed_transfer;
+pub use ssh_resumed_transfer::*;
+mod ssh_atomic_upload_options;
+pub use ssh_atomic_upload_options::*;
+mod ssh_ensure_file;
+pub use ssh_ensure_file::*;
+mod ssh_sync_options;
+pub use ssh_sync_options::*;
+mod ssh_archive_options;
+pub use ssh_archive_options::*;
+mod ssh_parallel_download_options;
+pub use ssh_parallel_download_options::*;
+mod checksum;
+use checksum::*;
+mod archive;
+mod atomic_upload;
+mod dir_transfer;
+mod download;
+mod ensure_file;
+mod parallel_download;
+mod resume;
+mod sync_dir;
+mod upload;
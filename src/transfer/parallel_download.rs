@@ -0,0 +1,170 @@
+use std::{
+    io::SeekFrom,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use futures::{AsyncReadExt, AsyncSeekExt};
+use tokio::io::{AsyncSeekExt as _, AsyncWriteExt};
+
+use crate::{SshSession, SshSessionError};
+
+use super::{
+    local_sha256, SshParallelDownloadOptions, SshRemoteFileInfo, SshTransferOptions,
+    SshTransferProgress,
+};
+
+struct DownloadState {
+    size: u64,
+    part_size: u64,
+    next_part: AtomicU64,
+    bytes_done: AtomicU64,
+    started: Instant,
+}
+
+impl SshSession {
+    // Downloads byte ranges of the file in parallel over several SSH connections and writes them in place.
+    // The result is verified by sha256 and the remote file must not change during the download
+    pub async fn download_parallel(
+        &self,
+        remote_path: &str,
+        local_path: impl AsRef<Path>,
+        options: &SshParallelDownloadOptions,
+    ) -> Result<SshRemoteFileInfo, SshSessionError> {
+        let transfer = &options.transfer;
+        let local_path = local_path.as_ref();
+
+        let sftp = self.sftp(transfer.inactivity_timeout).await?;
+        let stat = sftp.stat(remote_path).await?;
+        let info = SshRemoteFileInfo::from(&stat);
+
+        let local_file = tokio::fs::File::create(local_path).await?;
+        local_file.set_len(info.size).await?;
+        drop(local_file);
+
+        let state = DownloadState {
+            size: info.size,
+            part_size: options.part_size.max(1),
+            next_part: AtomicU64::new(0),
+            bytes_done: AtomicU64::new(0),
+            started: Instant::now(),
+        };
+
+        let parts = info.size.div_ceil(state.part_size);
+        let connections = (options.connections.max(1) as u64).min(parts.max(1)) as usize;
+
+        let extra_sessions: Vec<SshSession> = (1..connections)
+            .map(|_| SshSession::new(self.get_ssh_credentials().clone()))
+            .collect();
+
+        let mut workers = Vec::with_capacity(connections);
+        workers.push(download_ranges(
+            self,
+            remote_path,
+            local_path,
+            &state,
+            transfer,
+        ));
+
+        for session in extra_sessions.iter() {
+            workers.push(download_ranges(
+                session,
+                remote_path,
+                local_path,
+                &state,
+                transfer,
+            ));
+        }
+
+        let result = futures::future::try_join_all(workers).await;
+
+        for session in extra_sessions.iter() {
+            session.disconnect("Parallel download is finished").await;
+        }
+
+        result?;
+
+        let stat_after = sftp.stat(remote_path).await?;
+        if stat_after.size != stat.size || stat_after.mtime != stat.mtime {
+            return Err(SshSessionError::Other(format!(
+                "Remote file {} was changed during the download",
+                remote_path
+            )));
+        }
+
+        let local_hash = local_sha256(local_path, info.size).await?;
+        self.verify_remote_sha256(remote_path, None, local_hash, transfer)
+            .await?;
+
+        let local_file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(local_path)
+            .await?;
+        super::download::finish_local_file(local_file, &info, transfer).await?;
+
+        Ok(info)
+    }
+}
+
+// Takes the next free part until all the parts are taken
+async fn download_ranges(
+    session: &SshSession,
+    remote_path: &str,
+    local_path: &Path,
+    state: &DownloadState,
+    options: &SshTransferOptions,
+) -> Result<(), SshSessionError> {
+    let sftp = session.sftp(options.inactivity_timeout).await?;
+    let mut file = sftp.open(remote_path).await?;
+
+    let mut local_file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(local_path)
+        .await?;
+
+    let mut buf = vec![0u8; options.chunk_size.max(1)];
+
+    loop {
+        let offset = state.next_part.fetch_add(1, Ordering::SeqCst) * state.part_size;
+
+        if offset >= state.size {
+            break;
+        }
+
+        file.seek(SeekFrom::Start(offset)).await?;
+        local_file.seek(SeekFrom::Start(offset)).await?;
+
+        let mut remains = state.part_size.min(state.size - offset);
+
+        while remains > 0 {
+            let to_read = buf.len().min(remains as usize);
+            let size = super::wait_io(options, file.read(&mut buf[..to_read])).await?;
+
+            if size == 0 {
+                return Err(SshSessionError::Other(format!(
+                    "Remote file {} ended before {} bytes",
+                    remote_path, state.size
+                )));
+            }
+
+            super::wait_io(options, local_file.write_all(&buf[..size])).await?;
+            remains -= size as u64;
+
+            let bytes_done =
+                state.bytes_done.fetch_add(size as u64, Ordering::SeqCst) + size as u64;
+            let elapsed = state.started.elapsed();
+
+            options.report_progress(SshTransferProgress {
+                bytes_done,
+                total: Some(state.size),
+                bytes_per_second: bytes_done as f64 / elapsed.as_secs_f64().max(0.001),
+                elapsed,
+            });
+        }
+    }
+
+    super::wait_io(options, local_file.flush()).await?;
+
+    Ok(())
+}
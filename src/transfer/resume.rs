@@ -0,0 +1,213 @@
+use std::{io::SeekFrom, os::unix::fs::PermissionsExt, path::Path};
+
+use futures::AsyncSeekExt;
+use ssh2::{FileStat, OpenFlags};
+use tokio::io::{AsyncReadExt, AsyncSeekExt as _};
+
+use crate::{SshSession, SshSessionError};
+
+use super::{
+    local_sha256, FuturesWriteCompat, SshFileDownloadStream, SshRemoteFileInfo, SshResumedTransfer,
+    SshTransferOptions,
+};
+
+impl SshSession {
+    // Continues the download of a partially downloaded local file.
+    // Local content is kept only if it matches the beginning of the remote file by sha256, otherwise download starts over
+    pub async fn download_resumable(
+        &self,
+        remote_path: &str,
+        local_path: impl AsRef<Path>,
+        options: &SshTransferOptions,
+    ) -> Result<SshResumedTransfer, SshSessionError> {
+        let local_path = local_path.as_ref();
+
+        let sftp = self.sftp(options.inactivity_timeout).await?;
+        let info = SshRemoteFileInfo::from(&sftp.stat(remote_path).await?);
+
+        let local_size = match tokio::fs::metadata(local_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err.into()),
+        };
+
+        let offset = self
+            .get_resume_offset(local_path, remote_path, local_size, info.size, options)
+            .await?;
+
+        let mut file = sftp.open(remote_path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+
+        let mut local_file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(local_path)
+            .await?;
+        local_file.set_len(offset).await?;
+        local_file.seek(SeekFrom::Start(offset)).await?;
+
+        let mut stream = SshFileDownloadStream::new(info, file);
+        let copied = super::copy_stream_with_progress(
+            &mut stream,
+            &mut local_file,
+            options,
+            Some(info.size),
+            offset,
+        )
+        .await?;
+
+        if offset + copied < info.size {
+            return Err(SshSessionError::Other(format!(
+                "Remote file {} is {} bytes, but only {} bytes were downloaded",
+                remote_path,
+                info.size,
+                offset + copied
+            )));
+        }
+
+        super::download::finish_local_file(local_file, &info, options).await?;
+
+        // Resumed part was not streamed, so the whole local file is hashed
+        if options.verify_checksum {
+            let local_hash = local_sha256(local_path, offset + copied).await?;
+            self.verify_remote_sha256(remote_path, Some(offset + copied), local_hash, options)
+                .await?;
+        }
+
+        Ok(SshResumedTransfer {
+            size: offset + copied,
+            resumed_from: offset,
+            bytes_transferred: copied,
+        })
+    }
+
+    // Continues the upload of a partially uploaded remote file with SFTP.
+    // Remote content is kept only if it matches the beginning of the local file by sha256, otherwise upload starts over
+    pub async fn upload_resumable(
+        &self,
+        local_path: impl AsRef<Path>,
+        remote_path: &str,
+        options: &SshTransferOptions,
+    ) -> Result<SshResumedTransfer, SshSessionError> {
+        let local_path = local_path.as_ref();
+
+        let mut local_file = tokio::fs::File::open(local_path).await?;
+        let metadata = local_file.metadata().await?;
+        let size = metadata.len();
+        let mode = metadata.permissions().mode() & 0o7777;
+
+        let sftp = self.sftp(options.inactivity_timeout).await?;
+
+        let remote_size = match sftp.try_stat(remote_path).await? {
+            Some(stat) => stat.size.unwrap_or(0),
+            None => 0,
+        };
+
+        let offset = self
+            .get_resume_offset(local_path, remote_path, remote_size, size, options)
+            .await?;
+
+        let flags = if offset > 0 {
+            OpenFlags::WRITE
+        } else {
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE
+        };
+
+        let mut file = sftp
+            .open_with_flags(remote_path, flags, mode as i32)
+            .await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        local_file.seek(SeekFrom::Start(offset)).await?;
+
+        let mut writer = FuturesWriteCompat::new(file);
+        let copied = super::copy_stream_with_progress(
+            &mut local_file.take(size - offset),
+            &mut writer,
+            options,
+            Some(size),
+            offset,
+        )
+        .await?;
+
+        if offset + copied != size {
+            return Err(SshSessionError::Other(format!(
+                "Local file {:?} is {} bytes, but only {} bytes were uploaded",
+                local_path,
+                size,
+                offset + copied
+            )));
+        }
+
+        let mut file = writer.into_inner();
+
+        // fsync@openssh.com is not supported by every server
+        if tokio::time::timeout(options.inactivity_timeout, file.fsync())
+            .await
+            .is_err()
+        {
+            return Err(SshSessionError::Timeout);
+        }
+
+        drop(file);
+
+        if options.preserve_permissions || options.preserve_times {
+            let times = if options.preserve_times {
+                Some((
+                    super::upload::to_unix_seconds(metadata.modified()?),
+                    super::upload::to_unix_seconds(metadata.accessed()?),
+                ))
+            } else {
+                None
+            };
+
+            let stat = FileStat {
+                size: None,
+                uid: None,
+                gid: None,
+                perm: options.preserve_permissions.then_some(mode),
+                mtime: times.map(|(mtime, _)| mtime),
+                atime: times.map(|(_, atime)| atime),
+            };
+
+            sftp.set_stat(remote_path, stat).await?;
+        }
+
+        if options.verify_checksum {
+            let local_hash = local_sha256(local_path, size).await?;
+            self.verify_remote_sha256(remote_path, None, local_hash, options)
+                .await?;
+        }
+
+        Ok(SshResumedTransfer {
+            size,
+            resumed_from: offset,
+            bytes_transferred: copied,
+        })
+    }
+
+    // partial_size - size of the interrupted destination file, full_size - size of the source file
+    async fn get_resume_offset(
+        &self,
+        local_path: &Path,
+        remote_path: &str,
+        partial_size: u64,
+        full_size: u64,
+        options: &SshTransferOptions,
+    ) -> Result<u64, SshSessionError> {
+        if partial_size == 0 || partial_size > full_size {
+            return Ok(0);
+        }
+
+        let local_hash = local_sha256(local_path, partial_size).await?;
+        let remote_hash = self
+            .remote_sha256(remote_path, Some(partial_size), options.checksum_timeout)
+            .await?;
+
+        if local_hash == remote_hash {
+            Ok(partial_size)
+        } else {
+            Ok(0)
+        }
+    }
+}
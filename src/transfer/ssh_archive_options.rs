@@ -0,0 +1,26 @@
+use super::SshTransferOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshArchiveCompression {
+    None,
+    // Remote side needs gzip
+    Gzip,
+    // Remote side needs zstd
+    Zstd,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshArchiveOptions {
+    // Progress reports bytes of the archive stream; total is unknown
+    pub transfer: SshTransferOptions,
+    pub compression: SshArchiveCompression,
+}
+
+impl Default for SshArchiveOptions {
+    fn default() -> Self {
+        Self {
+            transfer: SshTransferOptions::default(),
+            compression: SshArchiveCompression::Gzip,
+        }
+    }
+}
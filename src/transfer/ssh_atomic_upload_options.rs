@@ -0,0 +1,22 @@
+use super::SshTransferOptions;
+
+#[derive(Debug, Clone)]
+pub struct SshAtomicUploadOptions {
+    pub transfer: SshTransferOptions,
+    pub mode: i32,
+    // (uid, gid). None - owner of the connected user
+    pub owner: Option<(u32, u32)>,
+    // Previous version is copied to <remote_path><suffix> before it is replaced
+    pub backup_suffix: Option<String>,
+}
+
+impl Default for SshAtomicUploadOptions {
+    fn default() -> Self {
+        Self {
+            transfer: SshTransferOptions::default(),
+            mode: 0o644,
+            owner: None,
+            backup_suffix: None,
+        }
+    }
+}
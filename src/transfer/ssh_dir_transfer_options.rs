@@ -0,0 +1,174 @@
+use glob::{MatchOptions, Pattern};
+
+use crate::SshSessionError;
+
+use super::SshTransferOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshSymlinkPolicy {
+    Skip,
+    // Symlink is transferred as the file or directory it points to. Cyclic links are not detected
+    Follow,
+    // Symlink is recreated on the destination with the same target
+    Preserve,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshDirTransferOptions {
+    // Used for every file. Permissions are always preserved
+    pub transfer: SshTransferOptions,
+    // Glob patterns. Pattern without '/' matches the file name, otherwise the path relative to the root.
+    // Empty include list means every file
+    pub include: Vec<String>,
+    // Excluded directories are not walked into
+    pub exclude: Vec<String>,
+    pub symlinks: SshSymlinkPolicy,
+    // Failed files are collected into the report instead of aborting the transfer
+    pub continue_on_error: bool,
+}
+
+impl Default for SshDirTransferOptions {
+    fn default() -> Self {
+        Self {
+            transfer: SshTransferOptions::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            symlinks: SshSymlinkPolicy::Skip,
+            continue_on_error: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SshDirTransferError {
+    // Relative to the root of the transfer
+    pub path: String,
+    pub error: SshSessionError,
+}
+
+#[derive(Debug, Default)]
+pub struct SshDirTransferReport {
+    pub files_transferred: usize,
+    pub bytes_transferred: u64,
+    pub dirs_created: usize,
+    pub symlinks_created: usize,
+    // Relative paths skipped because of filters or symlink policy
+    pub skipped: Vec<String>,
+    pub errors: Vec<SshDirTransferError>,
+}
+
+impl SshDirTransferReport {
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub(crate) fn handle_error(
+        &mut self,
+        options: &SshDirTransferOptions,
+        path: &str,
+        error: SshSessionError,
+    ) -> Result<(), SshSessionError> {
+        if !options.continue_on_error {
+            return Err(error);
+        }
+
+        self.errors.push(SshDirTransferError {
+            path: path.to_string(),
+            error,
+        });
+
+        Ok(())
+    }
+}
+
+pub(crate) struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, SshSessionError> {
+        Ok(Self {
+            include: compile_patterns(include)?,
+            exclude: compile_patterns(exclude)?,
+        })
+    }
+
+    pub fn is_excluded(&self, path: &str) -> bool {
+        self.exclude.iter().any(|pattern| matches(pattern, path))
+    }
+
+    pub fn is_file_included(&self, path: &str) -> bool {
+        if self.is_excluded(path) {
+            return false;
+        }
+
+        self.include.is_empty() || self.include.iter().any(|pattern| matches(pattern, path))
+    }
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, SshSessionError> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).map_err(|err| {
+                SshSessionError::Other(format!("Invalid glob pattern {}: {}", pattern, err))
+            })
+        })
+        .collect()
+}
+
+fn matches(pattern: &Pattern, path: &str) -> bool {
+    let match_options = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    if pattern.as_str().contains('/') {
+        return pattern.matches_with(path, match_options);
+    }
+
+    let name = path.rsplit('/').next().unwrap_or(path);
+    pattern.matches_with(name, match_options)
+}
+
+pub(crate) fn join_relative_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PathFilter, SshDirTransferOptions};
+
+    #[test]
+    fn test_path_filter() {
+        let options = SshDirTransferOptions {
+            include: vec!["*.rs".to_string(), "docs/**/*.md".to_string()],
+            exclude: vec!["target".to_string(), "src/generated/*".to_string()],
+            ..Default::default()
+        };
+
+        let filter = PathFilter::new(&options.include, &options.exclude).unwrap();
+
+        assert!(filter.is_file_included("main.rs"));
+        assert!(filter.is_file_included("src/ssh/mod.rs"));
+        assert!(filter.is_file_included("docs/api/index.md"));
+        assert!(!filter.is_file_included("README.md"));
+        assert!(!filter.is_file_included("src/generated/model.rs"));
+
+        assert!(filter.is_excluded("target"));
+        assert!(filter.is_excluded("sub/target"));
+        assert!(!filter.is_excluded("src"));
+    }
+
+    #[test]
+    fn test_empty_include_matches_everything() {
+        let filter = PathFilter::new(&[], &[]).unwrap();
+        assert!(filter.is_file_included("any/file.bin"));
+    }
+}
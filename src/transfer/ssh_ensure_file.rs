@@ -0,0 +1,40 @@
+use super::SshTransferOptions;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SshEnsureFileStatus {
+    Unchanged,
+    Created,
+    Updated,
+}
+
+#[derive(Debug, Clone)]
+pub struct SshEnsureFileOptions {
+    pub transfer: SshTransferOptions,
+    // Nothing is changed on the remote side; the result reports what would change
+    pub dry_run: bool,
+    // Applied when the content is replaced
+    pub backup_suffix: Option<String>,
+    // Diff is not built for bigger files
+    pub max_diff_size: usize,
+}
+
+impl Default for SshEnsureFileOptions {
+    fn default() -> Self {
+        Self {
+            transfer: SshTransferOptions::default(),
+            dry_run: false,
+            backup_suffix: None,
+            max_diff_size: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SshEnsureFileResult {
+    pub status: SshEnsureFileStatus,
+    pub content_changed: bool,
+    // Mode or owner
+    pub metadata_changed: bool,
+    // Unified diff. Only for text files when the content is changed
+    pub diff: Option<String>,
+}
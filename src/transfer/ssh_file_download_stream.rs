@@ -0,0 +1,46 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::ReadBuf;
+
+use crate::SshAsyncSftpFile;
+
+use super::SshRemoteFileInfo;
+
+// Remote file opened for reading. Implements tokio::io::AsyncRead.
+// Reads are not limited with timeouts here; SshSession::download_to_path applies inactivity timeout
+pub struct SshFileDownloadStream {
+    pub info: SshRemoteFileInfo,
+    file: SshAsyncSftpFile,
+}
+
+impl SshFileDownloadStream {
+    pub fn new(info: SshRemoteFileInfo, file: SshAsyncSftpFile) -> Self {
+        Self { info, file }
+    }
+
+    pub fn into_inner(self) -> SshAsyncSftpFile {
+        self.file
+    }
+}
+
+impl tokio::io::AsyncRead for SshFileDownloadStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let unfilled = buf.initialize_unfilled();
+
+        match futures::AsyncRead::poll_read(Pin::new(&mut self.file), cx, unfilled) {
+            Poll::Ready(Ok(size)) => {
+                buf.advance(size);
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
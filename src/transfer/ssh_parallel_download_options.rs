@@ -0,0 +1,20 @@
+use super::SshTransferOptions;
+
+#[derive(Debug, Clone)]
+pub struct SshParallelDownloadOptions {
+    pub transfer: SshTransferOptions,
+    // Every connection is a separate SSH session with its own TCP stream. This session is one of them
+    pub connections: usize,
+    // Byte range fetched by a connection at once
+    pub part_size: u64,
+}
+
+impl Default for SshParallelDownloadOptions {
+    fn default() -> Self {
+        Self {
+            transfer: SshTransferOptions::default(),
+            connections: 4,
+            part_size: 8 * 1024 * 1024,
+        }
+    }
+}
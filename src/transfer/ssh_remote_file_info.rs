@@ -0,0 +1,22 @@
+use ssh2::FileStat;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SshRemoteFileInfo {
+    pub size: u64,
+    // Permission bits only (0o755)
+    pub mode: u32,
+    // Unix timestamps in seconds
+    pub mtime: Option<u64>,
+    pub atime: Option<u64>,
+}
+
+impl From<&FileStat> for SshRemoteFileInfo {
+    fn from(stat: &FileStat) -> Self {
+        Self {
+            size: stat.size.unwrap_or(0),
+            mode: stat.perm.unwrap_or(0) & 0o7777,
+            mtime: stat.mtime,
+            atime: stat.atime,
+        }
+    }
+}
@@ -0,0 +1,7 @@
+#[derive(Debug, Clone, Copy)]
+pub struct SshResumedTransfer {
+    pub size: u64,
+    // Bytes which were already on the destination and matched by checksum. 0 - transfer started over
+    pub resumed_from: u64,
+    pub bytes_transferred: u64,
+}
@@ -0,0 +1,73 @@
+use super::{SshDirTransferError, SshTransferOptions};
+
+#[derive(Debug, Clone)]
+pub struct SshSyncOptions {
+    // Times and permissions are always preserved, so the next sync can compare mtimes
+    pub transfer: SshTransferOptions,
+    // Same rules as SshDirTransferOptions. Excluded and not included remote files are never deleted
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    // Local symlinks are synced as the files they point to; otherwise they are skipped
+    pub follow_symlinks: bool,
+    // Files with the same size are compared by sha256 instead of mtime
+    pub compare_checksum: bool,
+    // Removes remote files and directories which do not exist locally
+    pub delete_extraneous: bool,
+    // Nothing is changed on the remote side; the report shows what would change
+    pub dry_run: bool,
+    pub continue_on_error: bool,
+}
+
+impl Default for SshSyncOptions {
+    fn default() -> Self {
+        Self {
+            transfer: SshTransferOptions::default(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            follow_symlinks: false,
+            compare_checksum: false,
+            delete_extraneous: false,
+            dry_run: false,
+            continue_on_error: false,
+        }
+    }
+}
+
+// Paths are relative to the synced directories
+#[derive(Debug, Default)]
+pub struct SshSyncReport {
+    pub dirs_created: Vec<String>,
+    pub uploaded: Vec<String>,
+    pub deleted: Vec<String>,
+    pub unchanged: usize,
+    pub bytes_uploaded: u64,
+    pub errors: Vec<SshDirTransferError>,
+}
+
+impl SshSyncReport {
+    pub fn is_success(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn has_changes(&self) -> bool {
+        !self.dirs_created.is_empty() || !self.uploaded.is_empty() || !self.deleted.is_empty()
+    }
+
+    pub(crate) fn handle_error(
+        &mut self,
+        options: &SshSyncOptions,
+        path: &str,
+        error: crate::SshSessionError,
+    ) -> Result<(), crate::SshSessionError> {
+        if !options.continue_on_error {
+            return Err(error);
+        }
+
+        self.errors.push(SshDirTransferError {
+            path: path.to_string(),
+            error,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::watch;
+
+use super::{SshCancellationToken, SshTransferProgress};
+
+#[derive(Debug, Clone)]
+pub struct SshTransferOptions {
+    // Transfer fails if no byte is transferred during this time. Total duration is not limited
+    pub inactivity_timeout: Duration,
+    pub chunk_size: usize,
+    // Like `scp -p`: modification and access times are copied to the destination
+    pub preserve_times: bool,
+    // Permission bits are applied explicitly, so umask and mode of an existing file do not matter
+    pub preserve_permissions: bool,
+    // Limits hashing of the remote file, which does not report any progress
+    pub checksum_timeout: Duration,
+    // Data is hashed with sha256 while streaming and compared with the hash calculated on the remote side
+    pub verify_checksum: bool,
+    pub cancellation: Option<SshCancellationToken>,
+    // Set by subscribe_progress. Public only so options can be built with ..Default::default()
+    pub progress: Option<Arc<watch::Sender<SshTransferProgress>>>,
+}
+
+impl Default for SshTransferOptions {
+    fn default() -> Self {
+        Self {
+            inactivity_timeout: Duration::from_secs(30),
+            chunk_size: 256 * 1024,
+            preserve_times: false,
+            preserve_permissions: false,
+            checksum_timeout: Duration::from_secs(300),
+            verify_checksum: false,
+            cancellation: None,
+            progress: None,
+        }
+    }
+}
+
+impl SshTransferOptions {
+    // Progress is published after every chunk. Options cloned afterwards report to the same receiver
+    pub fn subscribe_progress(&mut self) -> watch::Receiver<SshTransferProgress> {
+        let (sender, receiver) = watch::channel(SshTransferProgress::default());
+        self.progress = Some(Arc::new(sender));
+        receiver
+    }
+
+    pub(crate) fn report_progress(&self, progress: SshTransferProgress) {
+        if let Some(sender) = self.progress.as_ref() {
+            sender.send_replace(progress);
+        }
+    }
+}
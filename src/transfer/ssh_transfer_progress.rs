@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SshTransferProgress {
+    // Includes the part skipped by resumed transfers
+    pub bytes_done: u64,
+    // None when size of the source is not known in advance
+    pub total: Option<u64>,
+    // Measured over the bytes transferred by this call only
+    pub bytes_per_second: f64,
+    pub elapsed: Duration,
+}
+
+// Cancels running transfers which got a clone of the token.
+// Transfer stops with SshSessionError::Cancelled, its channel is closed; the session stays connected
+#[derive(Debug, Clone)]
+pub struct SshCancellationToken {
+    sender: std::sync::Arc<watch::Sender<bool>>,
+}
+
+impl SshCancellationToken {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: std::sync::Arc::new(sender),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    pub async fn cancelled(&self) {
+        let mut receiver = self.sender.subscribe();
+
+        while !*receiver.borrow_and_update() {
+            if receiver.changed().await.is_err() {
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl Default for SshCancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SshCancellationToken;
+
+    #[tokio::test]
+    async fn test_cancelled() {
+        let token = SshCancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+
+        let waiter = clone.cancelled();
+        futures::pin_mut!(waiter);
+        assert!(futures::poll!(&mut waiter).is_pending());
+
+        token.cancel();
+        waiter.await;
+        assert!(clone.is_cancelled());
+
+        // Token cancelled before the call completes right away
+        clone.cancelled().await;
+    }
+}
@@ -0,0 +1,695 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{SshSession, SshSessionError, SshSftp};
+
+use super::{
+    join_relative_path, local_sha256, PathFilter, SshSyncOptions, SshSyncReport, SshTransferOptions,
+};
+
+const DEFAULT_DIR_MODE: i32 = 0o755;
+
+enum LocalEntry {
+    Dir,
+    File {
+        path: PathBuf,
+        size: u64,
+        mtime: u64,
+    },
+}
+
+enum RemoteEntry {
+    Dir,
+    File { size: u64, mtime: Option<u64> },
+    Symlink,
+    // Excluded or not included by filters. Never compared or deleted
+    Protected,
+}
+
+#[derive(Debug, PartialEq)]
+enum SyncAction<'s> {
+    // replace - remote entry with the same name is not a directory
+    CreateDir {
+        replace: bool,
+    },
+    Upload {
+        local_path: &'s Path,
+        size: u64,
+        replace_symlink: bool,
+    },
+    // Same size. Uploaded only if sha256 differs
+    CompareChecksum {
+        local_path: &'s Path,
+        size: u64,
+    },
+    Unchanged,
+    DeleteFile,
+    DeleteDir,
+    // Syncing the entry would touch remote entries protected by filters
+    Blocked {
+        reason: &'static str,
+    },
+}
+
+impl SshSession {
+    // One-way sync of the local directory into the remote one. Only new and changed files are uploaded.
+    // Files are compared by size and mtime, or by size and sha256 with compare_checksum
+    pub async fn sync_dir(
+        &self,
+        local_path: impl AsRef<Path>,
+        remote_path: &str,
+        options: &SshSyncOptions,
+    ) -> Result<SshSyncReport, SshSessionError> {
+        let filter = PathFilter::new(&options.include, &options.exclude)?;
+        let sftp = self.sftp(options.transfer.inactivity_timeout).await?;
+        let remote_root = remote_path.trim_end_matches('/').to_string();
+
+        let local = read_local_tree(local_path.as_ref(), &filter, options).await?;
+        let remote = if sftp.exists(remote_root.as_str()).await? {
+            read_remote_tree(&sftp, remote_root.as_str(), &filter).await?
+        } else {
+            if !options.dry_run {
+                sftp.mkdir_all(remote_root.as_str(), DEFAULT_DIR_MODE)
+                    .await?;
+            }
+            BTreeMap::new()
+        };
+
+        let mut report = SshSyncReport::default();
+
+        for (relative_path, action) in plan_sync(&local, &remote, options) {
+            let remote_full_path = format!("{}/{}", remote_root, relative_path);
+
+            let result = self
+                .apply_sync_action(
+                    &sftp,
+                    relative_path,
+                    remote_full_path.as_str(),
+                    action,
+                    options,
+                    &mut report,
+                )
+                .await;
+
+            if let Err(err) = result {
+                report.handle_error(options, relative_path, err)?;
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn apply_sync_action(
+        &self,
+        sftp: &SshSftp,
+        relative_path: &str,
+        remote_full_path: &str,
+        action: SyncAction<'_>,
+        options: &SshSyncOptions,
+        report: &mut SshSyncReport,
+    ) -> Result<(), SshSessionError> {
+        let (local_path, size, replace_symlink) = match action {
+            SyncAction::CreateDir { replace } => {
+                report.dirs_created.push(relative_path.to_string());
+
+                if !options.dry_run {
+                    if replace {
+                        sftp.remove_file(remote_full_path).await?;
+                    }
+                    sftp.mkdir(remote_full_path, DEFAULT_DIR_MODE).await?;
+                }
+
+                return Ok(());
+            }
+            SyncAction::Unchanged => {
+                report.unchanged += 1;
+                return Ok(());
+            }
+            SyncAction::CompareChecksum { local_path, size } => {
+                let local_hash = local_sha256(local_path, size).await?;
+                let remote_hash = self
+                    .remote_sha256(remote_full_path, None, options.transfer.checksum_timeout)
+                    .await?;
+
+                if local_hash == remote_hash {
+                    report.unchanged += 1;
+                    return Ok(());
+                }
+
+                (local_path, size, false)
+            }
+            SyncAction::Upload {
+                local_path,
+                size,
+                replace_symlink,
+            } => (local_path, size, replace_symlink),
+            SyncAction::DeleteFile => {
+                report.deleted.push(relative_path.to_string());
+
+                if !options.dry_run {
+                    sftp.remove_file(remote_full_path).await?;
+                }
+
+                return Ok(());
+            }
+            SyncAction::DeleteDir => {
+                report.deleted.push(relative_path.to_string());
+
+                if !options.dry_run {
+                    sftp.remove_dir(remote_full_path).await?;
+                }
+
+                return Ok(());
+            }
+            SyncAction::Blocked { reason } => {
+                return Err(SshSessionError::Other(format!(
+                    "Can not sync {}. Remote entry {}",
+                    remote_full_path, reason
+                )));
+            }
+        };
+
+        report.uploaded.push(relative_path.to_string());
+        report.bytes_uploaded += size;
+
+        if options.dry_run {
+            return Ok(());
+        }
+
+        if replace_symlink {
+            sftp.remove_file(remote_full_path).await?;
+        }
+
+        let transfer_options = SshTransferOptions {
+            preserve_times: true,
+            preserve_permissions: true,
+            ..options.transfer.clone()
+        };
+
+        let exit_code = self
+            .upload_from_path(local_path, remote_full_path, &transfer_options)
+            .await?;
+
+        if exit_code != 0 {
+            return Err(SshSessionError::Other(format!(
+                "Upload of {} finished with exit code {}",
+                remote_full_path, exit_code
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+// Decides what has to be done with every entry. Nothing is read or changed here,
+// the only remote check left for later is sha256 of the files with the same size
+fn plan_sync<'s>(
+    local: &'s BTreeMap<String, LocalEntry>,
+    remote: &'s BTreeMap<String, RemoteEntry>,
+    options: &SshSyncOptions,
+) -> Vec<(&'s str, SyncAction<'s>)> {
+    let mut result = Vec::new();
+    let mut deleted = HashSet::new();
+    // Directories which can not be created, so nothing is synced inside of them
+    let mut blocked: Vec<String> = Vec::new();
+
+    // BTreeMap order puts parents before children
+    for (relative_path, local_entry) in local.iter() {
+        if blocked
+            .iter()
+            .any(|prefix| relative_path.starts_with(prefix.as_str()))
+        {
+            continue;
+        }
+
+        let remote_entry = remote.get(relative_path);
+
+        let action = match local_entry {
+            LocalEntry::Dir => match remote_entry {
+                Some(RemoteEntry::Dir) => continue,
+                Some(RemoteEntry::Protected) => {
+                    blocked.push(format!("{}/", relative_path));
+                    SyncAction::Blocked {
+                        reason: "is protected by filters",
+                    }
+                }
+                Some(_) => SyncAction::CreateDir { replace: true },
+                None => SyncAction::CreateDir { replace: false },
+            },
+            LocalEntry::File { path, size, mtime } => {
+                let upload = SyncAction::Upload {
+                    local_path: path.as_path(),
+                    size: *size,
+                    replace_symlink: false,
+                };
+
+                match remote_entry {
+                    Some(RemoteEntry::File {
+                        size: remote_size,
+                        mtime: remote_mtime,
+                    }) => {
+                        if remote_size != size {
+                            upload
+                        } else if options.compare_checksum {
+                            SyncAction::CompareChecksum {
+                                local_path: path.as_path(),
+                                size: *size,
+                            }
+                        } else if *remote_mtime == Some(*mtime) {
+                            SyncAction::Unchanged
+                        } else {
+                            upload
+                        }
+                    }
+                    // scp writes through a symlink, so the link is removed first
+                    Some(RemoteEntry::Symlink) => SyncAction::Upload {
+                        local_path: path.as_path(),
+                        size: *size,
+                        replace_symlink: true,
+                    },
+                    // scp -t <dir> writes the file inside of the directory, so the directory goes first
+                    Some(RemoteEntry::Dir) => {
+                        let prefix = format!("{}/", relative_path);
+                        let children: Vec<_> = remote
+                            .range(prefix.clone()..)
+                            .take_while(|(other, _)| other.starts_with(prefix.as_str()))
+                            .collect();
+
+                        if children
+                            .iter()
+                            .any(|(_, entry)| matches!(entry, RemoteEntry::Protected))
+                        {
+                            SyncAction::Blocked {
+                                reason: "is a directory with entries protected by filters",
+                            }
+                        } else {
+                            for (child_path, child) in children.into_iter().rev() {
+                                let action = match child {
+                                    RemoteEntry::Dir => SyncAction::DeleteDir,
+                                    _ => SyncAction::DeleteFile,
+                                };
+
+                                deleted.insert(child_path.as_str());
+                                result.push((child_path.as_str(), action));
+                            }
+
+                            deleted.insert(relative_path.as_str());
+                            result.push((relative_path.as_str(), SyncAction::DeleteDir));
+                            upload
+                        }
+                    }
+                    _ => upload,
+                }
+            }
+        };
+
+        result.push((relative_path.as_str(), action));
+    }
+
+    if options.delete_extraneous {
+        plan_deletion(local, remote, &mut deleted, &mut result);
+    }
+
+    result
+}
+
+fn plan_deletion<'s>(
+    local: &'s BTreeMap<String, LocalEntry>,
+    remote: &'s BTreeMap<String, RemoteEntry>,
+    deleted: &mut HashSet<&'s str>,
+    result: &mut Vec<(&'s str, SyncAction<'s>)>,
+) {
+    // Children go before their parents
+    for (relative_path, remote_entry) in remote.iter().rev() {
+        if local.contains_key(relative_path) || deleted.contains(relative_path.as_str()) {
+            continue;
+        }
+
+        let action = match remote_entry {
+            RemoteEntry::Protected => continue,
+            RemoteEntry::Dir => {
+                // Directory still has entries protected by filters
+                let prefix = format!("{}/", relative_path);
+                let has_kept_children = remote.keys().any(|other| {
+                    other.starts_with(prefix.as_str()) && !deleted.contains(other.as_str())
+                });
+
+                if has_kept_children {
+                    continue;
+                }
+
+                SyncAction::DeleteDir
+            }
+            _ => SyncAction::DeleteFile,
+        };
+
+        deleted.insert(relative_path.as_str());
+        result.push((relative_path.as_str(), action));
+    }
+}
+
+async fn read_local_tree(
+    local_root: &Path,
+    filter: &PathFilter,
+    options: &SshSyncOptions,
+) -> Result<BTreeMap<String, LocalEntry>, SshSessionError> {
+    let mut result = BTreeMap::new();
+    let mut dirs = vec![(local_root.to_path_buf(), String::new())];
+
+    while let Some((local_dir, relative_dir)) = dirs.pop() {
+        let mut read_dir = tokio::fs::read_dir(&local_dir).await?;
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative_path = join_relative_path(relative_dir.as_str(), name.as_str());
+
+            if filter.is_excluded(relative_path.as_str()) {
+                continue;
+            }
+
+            let mut metadata = tokio::fs::symlink_metadata(entry.path()).await?;
+
+            if metadata.file_type().is_symlink() {
+                if !options.follow_symlinks {
+                    continue;
+                }
+
+                metadata = tokio::fs::metadata(entry.path()).await?;
+            }
+
+            if metadata.is_dir() {
+                dirs.push((entry.path(), relative_path.clone()));
+                result.insert(relative_path, LocalEntry::Dir);
+            } else if filter.is_file_included(relative_path.as_str()) {
+                result.insert(
+                    relative_path,
+                    LocalEntry::File {
+                        path: entry.path(),
+                        size: metadata.len(),
+                        mtime: super::upload::to_unix_seconds(metadata.modified()?),
+                    },
+                );
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+// Excluded directories are not walked
+async fn read_remote_tree(
+    sftp: &SshSftp,
+    remote_root: &str,
+    filter: &PathFilter,
+) -> Result<BTreeMap<String, RemoteEntry>, SshSessionError> {
+    let mut result = BTreeMap::new();
+    let mut dirs = vec![(remote_root.to_string(), String::new())];
+
+    while let Some((remote_dir, relative_dir)) = dirs.pop() {
+        for entry in sftp.list_dir(remote_dir.as_str()).await? {
+            let relative_path = join_relative_path(relative_dir.as_str(), entry.name.as_str());
+
+            if filter.is_excluded(relative_path.as_str()) {
+                result.insert(relative_path, RemoteEntry::Protected);
+                continue;
+            }
+
+            if entry.is_dir() {
+                dirs.push((entry.path.clone(), relative_path.clone()));
+                result.insert(relative_path, RemoteEntry::Dir);
+            } else if !filter.is_file_included(relative_path.as_str()) {
+                result.insert(relative_path, RemoteEntry::Protected);
+            } else {
+                let remote_entry = if entry.is_symlink() {
+                    RemoteEntry::Symlink
+                } else {
+                    RemoteEntry::File {
+                        size: entry.stat.size.unwrap_or(0),
+                        mtime: entry.stat.mtime,
+                    }
+                };
+
+                result.insert(relative_path, remote_entry);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::Path};
+
+    use super::{plan_sync, LocalEntry, RemoteEntry, SyncAction};
+    use crate::SshSyncOptions;
+
+    fn local_file(path: &str, size: u64, mtime: u64) -> (String, LocalEntry) {
+        let entry = LocalEntry::File {
+            path: Path::new("/local").join(path),
+            size,
+            mtime,
+        };
+
+        (path.to_string(), entry)
+    }
+
+    fn remote_file(path: &str, size: u64, mtime: u64) -> (String, RemoteEntry) {
+        let entry = RemoteEntry::File {
+            size,
+            mtime: Some(mtime),
+        };
+
+        (path.to_string(), entry)
+    }
+
+    fn entry<T>(path: &str, entry: T) -> (String, T) {
+        (path.to_string(), entry)
+    }
+
+    #[test]
+    fn test_create_update_skip() {
+        let local = BTreeMap::from([
+            entry("conf", LocalEntry::Dir),
+            local_file("conf/app.toml", 10, 100),
+            local_file("new.txt", 5, 100),
+            local_file("resized.txt", 7, 100),
+            local_file("touched.txt", 3, 200),
+            local_file("link.txt", 3, 100),
+        ]);
+
+        let remote = BTreeMap::from([
+            remote_file("conf/app.toml", 10, 100),
+            remote_file("resized.txt", 6, 100),
+            remote_file("touched.txt", 3, 100),
+            entry("link.txt", RemoteEntry::Symlink),
+        ]);
+
+        let plan = plan_sync(&local, &remote, &SshSyncOptions::default());
+
+        assert_eq!(
+            plan,
+            vec![
+                ("conf", SyncAction::CreateDir { replace: false }),
+                ("conf/app.toml", SyncAction::Unchanged),
+                (
+                    "link.txt",
+                    SyncAction::Upload {
+                        local_path: Path::new("/local/link.txt"),
+                        size: 3,
+                        replace_symlink: true,
+                    }
+                ),
+                (
+                    "new.txt",
+                    SyncAction::Upload {
+                        local_path: Path::new("/local/new.txt"),
+                        size: 5,
+                        replace_symlink: false,
+                    }
+                ),
+                (
+                    "resized.txt",
+                    SyncAction::Upload {
+                        local_path: Path::new("/local/resized.txt"),
+                        size: 7,
+                        replace_symlink: false,
+                    }
+                ),
+                (
+                    "touched.txt",
+                    SyncAction::Upload {
+                        local_path: Path::new("/local/touched.txt"),
+                        size: 3,
+                        replace_symlink: false,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_checksum_ignores_mtime() {
+        let local = BTreeMap::from([local_file("a.txt", 3, 200), local_file("b.txt", 4, 100)]);
+        let remote = BTreeMap::from([remote_file("a.txt", 3, 100), remote_file("b.txt", 3, 100)]);
+
+        let options = SshSyncOptions {
+            compare_checksum: true,
+            ..Default::default()
+        };
+
+        let plan = plan_sync(&local, &remote, &options);
+
+        assert_eq!(
+            plan[0],
+            (
+                "a.txt",
+                SyncAction::CompareChecksum {
+                    local_path: Path::new("/local/a.txt"),
+                    size: 3,
+                }
+            )
+        );
+        // Different size needs no checksum
+        assert!(matches!(plan[1], ("b.txt", SyncAction::Upload { .. })));
+    }
+
+    #[test]
+    fn test_dir_replaces_remote_file() {
+        let local = BTreeMap::from([
+            entry("data", LocalEntry::Dir),
+            entry("logs", LocalEntry::Dir),
+            local_file("logs/app.log", 1, 100),
+        ]);
+        let remote = BTreeMap::from([
+            remote_file("data", 1, 100),
+            entry("logs", RemoteEntry::Protected),
+        ]);
+
+        let plan = plan_sync(&local, &remote, &SshSyncOptions::default());
+
+        // Protected file is never removed, so nothing goes into the directory
+        assert_eq!(
+            plan,
+            vec![
+                ("data", SyncAction::CreateDir { replace: true }),
+                (
+                    "logs",
+                    SyncAction::Blocked {
+                        reason: "is protected by filters"
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_replaces_remote_dir() {
+        let local = BTreeMap::from([local_file("data", 3, 100), local_file("logs", 3, 100)]);
+        let remote = BTreeMap::from([
+            entry("data", RemoteEntry::Dir),
+            entry("data/nested", RemoteEntry::Dir),
+            remote_file("data/nested/a.txt", 1, 100),
+            entry("logs", RemoteEntry::Dir),
+            entry("logs/app.log", RemoteEntry::Protected),
+        ]);
+
+        let options = SshSyncOptions {
+            delete_extraneous: true,
+            ..Default::default()
+        };
+
+        let plan = plan_sync(&local, &remote, &options);
+
+        assert_eq!(
+            plan,
+            vec![
+                ("data/nested/a.txt", SyncAction::DeleteFile),
+                ("data/nested", SyncAction::DeleteDir),
+                ("data", SyncAction::DeleteDir),
+                (
+                    "data",
+                    SyncAction::Upload {
+                        local_path: Path::new("/local/data"),
+                        size: 3,
+                        replace_symlink: false,
+                    }
+                ),
+                (
+                    "logs",
+                    SyncAction::Blocked {
+                        reason: "is a directory with entries protected by filters"
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_children_are_deleted_before_parents() {
+        let local = BTreeMap::from([local_file("keep.txt", 1, 100)]);
+        let remote = BTreeMap::from([
+            remote_file("keep.txt", 1, 100),
+            entry("old", RemoteEntry::Dir),
+            entry("old/nested", RemoteEntry::Dir),
+            remote_file("old/nested/a.txt", 1, 100),
+            remote_file("old-file.txt", 1, 100),
+        ]);
+
+        let options = SshSyncOptions {
+            delete_extraneous: true,
+            ..Default::default()
+        };
+
+        let plan = plan_sync(&local, &remote, &options);
+
+        assert_eq!(
+            plan,
+            vec![
+                ("keep.txt", SyncAction::Unchanged),
+                ("old/nested/a.txt", SyncAction::DeleteFile),
+                ("old/nested", SyncAction::DeleteDir),
+                ("old-file.txt", SyncAction::DeleteFile),
+                ("old", SyncAction::DeleteDir),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_protected_entries_are_kept() {
+        let local = BTreeMap::new();
+        let remote = BTreeMap::from([
+            entry("logs", RemoteEntry::Dir),
+            entry("logs/app.log", RemoteEntry::Protected),
+            remote_file("logs/old.txt", 1, 100),
+            entry("cache", RemoteEntry::Protected),
+            entry("tmp", RemoteEntry::Dir),
+        ]);
+
+        let options = SshSyncOptions {
+            delete_extraneous: true,
+            ..Default::default()
+        };
+
+        let plan = plan_sync(&local, &remote, &options);
+
+        // logs keeps a protected child, so only its unprotected file goes
+        assert_eq!(
+            plan,
+            vec![
+                ("tmp", SyncAction::DeleteDir),
+                ("logs/old.txt", SyncAction::DeleteFile),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nothing_is_deleted_by_default() {
+        let local = BTreeMap::new();
+        let remote = BTreeMap::from([remote_file("a.txt", 1, 100)]);
+
+        assert!(plan_sync(&local, &remote, &SshSyncOptions::default()).is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+use std::{os::unix::fs::PermissionsExt, path::Path, time::SystemTime};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{to_sftp_path, SshSession, SshSessionError};
+
+use super::{FuturesWriteCompat, HashingReader, SshTransferOptions};
+
+impl SshSession {
+    // Uploads exactly size bytes taken from the reader with SCP.
+    // Transfer is limited with inactivity timeout, so large files are not cut by total duration
+    pub async fn upload_stream(
+        &self,
+        remote_path: &str,
+        reader: &mut (impl AsyncRead + Unpin),
+        size: u64,
+        mode: i32,
+        options: &SshTransferOptions,
+    ) -> Result<i32, SshSessionError> {
+        let (exit_code, _) = self
+            .upload_stream_with_times(remote_path, reader, size, mode, None, options)
+            .await?;
+        Ok(exit_code)
+    }
+
+    // times - (mtime, atime) in unix seconds.
+    // Returns sha256 of the uploaded data as well, if it was computed for verify_checksum
+    pub(super) async fn upload_stream_with_times(
+        &self,
+        remote_path: &str,
+        reader: &mut (impl AsyncRead + Unpin),
+        size: u64,
+        mode: i32,
+        times: Option<(u64, u64)>,
+        options: &SshTransferOptions,
+    ) -> Result<(i32, Option<String>), SshSessionError> {
+        let channel = {
+            let mut write_access = self.inner.inner.lock().await;
+            let ssh_session = write_access.get(&self.inner.credentials).await?;
+            let future = ssh_session.open_scp_send(to_sftp_path(remote_path), mode, size, times);
+            self.inner
+                .execute_with_timeout(&mut write_access, future, options.inactivity_timeout)
+                .await?
+        };
+
+        let mut writer = FuturesWriteCompat::new(channel);
+        let mut reader = HashingReader::new(reader.take(size), options.verify_checksum);
+
+        let copied =
+            super::copy_stream_with_progress(&mut reader, &mut writer, options, Some(size), 0)
+                .await;
+
+        let copied = match copied {
+            Ok(copied) => copied,
+            Err(err) => {
+                // Closing the channel aborts remote scp; the session stays usable
+                let channel = writer.get_mut();
+                let _ = tokio::time::timeout(options.inactivity_timeout, channel.close()).await;
+                return Err(err);
+            }
+        };
+
+        if copied != size {
+            return Err(SshSessionError::Other(format!(
+                "Upload of {} declared {} bytes, but the stream ended after {} bytes",
+                remote_path, size, copied
+            )));
+        }
+
+        let mut channel = writer.into_inner();
+
+        // Close the channel and wait for the whole content to be transferred
+        let finish = async {
+            channel.send_eof().await?;
+            channel.wait_eof().await?;
+            channel.close().await?;
+            channel.wait_close().await?;
+            Ok::<_, SshSessionError>(channel.exit_status()?)
+        };
+
+        let exit_code = match tokio::time::timeout(options.inactivity_timeout, finish).await {
+            Ok(result) => result?,
+            Err(_) => return Err(SshSessionError::Timeout),
+        };
+
+        let digest = reader.finish();
+
+        if exit_code != 0 {
+            return Ok((exit_code, digest));
+        }
+
+        // scp applies the mode only to new files and through umask
+        if options.preserve_permissions {
+            let sftp = self.sftp(options.inactivity_timeout).await?;
+            sftp.chmod(remote_path, mode as u32).await?;
+        }
+
+        if let Some(local_hash) = digest.as_ref() {
+            self.verify_remote_sha256(remote_path, None, local_hash.clone(), options)
+                .await?;
+        }
+
+        Ok((exit_code, digest))
+    }
+
+    // Size and permissions are taken from the local file
+    pub async fn upload_from_path(
+        &self,
+        local_path: impl AsRef<Path>,
+        remote_path: &str,
+        options: &SshTransferOptions,
+    ) -> Result<i32, SshSessionError> {
+        let mut local_file = tokio::fs::File::open(local_path.as_ref()).await?;
+        let metadata = local_file.metadata().await?;
+
+        let mode = (metadata.permissions().mode() & 0o7777) as i32;
+
+        let times = if options.preserve_times {
+            Some((
+                to_unix_seconds(metadata.modified()?),
+                to_unix_seconds(metadata.accessed()?),
+            ))
+        } else {
+            None
+        };
+
+        let (exit_code, _) = self
+            .upload_stream_with_times(
+                remote_path,
+                &mut local_file,
+                metadata.len(),
+                mode,
+                times,
+                options,
+            )
+            .await?;
+
+        Ok(exit_code)
+    }
+}
+
+pub(super) fn to_unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}